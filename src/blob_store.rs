@@ -0,0 +1,329 @@
+// Content-addressed storage for cached mod files.
+// Every file is stored once in `[Cache Dir]/.blobs/[first 2 chars of hash]/[hash]`, and each version directory
+// is made of hardlinks to those blobs (or plain copies if the filesystem can't hardlink).
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use color_eyre::eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::config_and_such::Config;
+
+/// Directory inside the mod cache that holds the blobs
+const BLOB_DIR_NAME: &str = ".blobs";
+
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+impl BlobStore {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            root: config.mod_cache_directory.join(BLOB_DIR_NAME),
+        }
+    }
+
+    pub fn hash_bytes(bytes: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(bytes))
+    }
+
+    pub fn hash_file(path: &Path) -> Result<String> {
+        let mut hasher = Sha256::new();
+        let mut file = fs::File::open(path)?;
+        std::io::copy(&mut file, &mut hasher)?;
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.root.join(&hash[..2]).join(hash)
+    }
+
+    /// Moves every file in `dir` into the store and replaces it with a link to the stored copy.
+    /// Returns the hash and size of each file, keyed by its path relative to `dir`
+    pub fn ingest_directory(&self, dir: &Path) -> Result<BTreeMap<String, ManifestFile>> {
+        let mut files = BTreeMap::new();
+        for path in list_files(dir)? {
+            let relative = relative_key(dir, &path)?;
+            let size = fs::metadata(&path)?.len();
+            let hash = BlobStore::hash_file(&path)?;
+            let blob = self.blob_path(&hash);
+            if blob.exists() {
+                fs::remove_file(&path)?;
+            } else {
+                fs::create_dir_all(blob.parent().ok_or(eyre!("blob path has no parent"))?)?;
+                // rename is free since the store lives in the cache directory, but fall back just in case
+                if fs::rename(&path, &blob).is_err() {
+                    fs::copy(&path, &blob)?;
+                    fs::remove_file(&path)?;
+                }
+            }
+            self.link_blob(&hash, &path)?;
+            files.insert(relative, ManifestFile { hash, size });
+        }
+        Ok(files)
+    }
+
+    /// Puts a copy of the blob at `destination`, hardlinking when possible
+    fn link_blob(&self, hash: &str, destination: &Path) -> Result<()> {
+        let blob = self.blob_path(hash);
+        if fs::hard_link(&blob, destination).is_err() {
+            fs::copy(&blob, destination)?;
+        }
+        Ok(())
+    }
+
+    /// Deletes one blob, like when its contents turned out to be damaged. Links to it keep their copy
+    pub fn remove_blob(&self, hash: &str) -> Result<()> {
+        let blob = self.blob_path(hash);
+        if blob.exists() {
+            fs::remove_file(blob)?;
+        }
+        Ok(())
+    }
+
+    /// Space used by every blob together
+    pub fn total_size(&self) -> Result<u64> {
+        if !self.root.exists() {
+            return Ok(0);
+        }
+        let mut total = 0;
+        for blob in list_files(&self.root)? {
+            total += fs::metadata(&blob)?.len();
+        }
+        Ok(total)
+    }
+
+    /// Deletes every blob that isn't in `referenced`. Returns the number of bytes freed
+    pub fn remove_unreferenced(&self, referenced: &HashSet<String>) -> Result<u64> {
+        if !self.root.exists() {
+            return Ok(0);
+        }
+        let mut freed = 0;
+        for blob in list_files(&self.root)? {
+            let is_referenced = blob
+                .file_name()
+                .and_then(|x| x.to_str())
+                .is_some_and(|x| referenced.contains(x));
+            if !is_referenced {
+                freed += fs::metadata(&blob)?.len();
+                fs::remove_file(&blob)?;
+            }
+        }
+        Ok(freed)
+    }
+}
+
+/// Describes one file in a cached version
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub hash: String,
+    pub size: u64,
+}
+
+/// Lists the files of a cached version, stored at `[Cache Dir]/[Mod ID]/manifests/[version].json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VersionManifest {
+    /// Hash of the zip downloaded from Thunderstore. Missing for versions cached before manifests existed
+    pub archive_hash: Option<String>,
+    /// Keyed by the path inside the version directory, always using `/`, like `Mods/SomeMod.dll`
+    pub files: BTreeMap<String, ManifestFile>,
+}
+
+impl VersionManifest {
+    pub fn path(config: &Config, mod_id: &Uuid, version: &str) -> PathBuf {
+        config
+            .mod_cache_directory
+            .join(mod_id.to_string())
+            .join("manifests")
+            .join(format!("{version}.json"))
+    }
+
+    /// Returns `None` if the version has no manifest yet
+    pub fn load(config: &Config, mod_id: &Uuid, version: &str) -> Option<Self> {
+        let contents = fs::read_to_string(Self::path(config, mod_id, version)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn save(&self, config: &Config, mod_id: &Uuid, version: &str) -> Result<()> {
+        let path = Self::path(config, mod_id, version);
+        fs::create_dir_all(path.parent().ok_or(eyre!("manifest path has no parent"))?)?;
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Total size of the version's files, counting shared files once per version
+    pub fn total_size(&self) -> u64 {
+        self.files.values().map(|x| x.size).sum()
+    }
+
+    /// Loads the manifest of every cached version, along with its mod ID and version number
+    pub fn load_all(config: &Config) -> Result<Vec<(Uuid, String, VersionManifest)>> {
+        let mut manifests = vec![];
+        for mod_dir in fs::read_dir(&config.mod_cache_directory)? {
+            let mod_dir = mod_dir?.path();
+            let manifest_dir = mod_dir.join("manifests");
+            let Some(mod_id) = mod_dir
+                .file_name()
+                .and_then(|x| Uuid::parse_str(&x.to_string_lossy()).ok())
+            else {
+                continue;
+            };
+            if !manifest_dir.is_dir() {
+                continue;
+            }
+            for manifest_path in fs::read_dir(manifest_dir)? {
+                let manifest_path = manifest_path?.path();
+                let Some(version) = manifest_path.file_stem().map(|x| x.to_string_lossy().to_string())
+                else {
+                    continue;
+                };
+                let contents = fs::read_to_string(&manifest_path)?;
+                manifests.push((mod_id, version, serde_json::from_str(&contents)?));
+            }
+        }
+        Ok(manifests)
+    }
+
+    /// Gets every blob hash used by any cached version
+    pub fn get_all_referenced_hashes(config: &Config) -> Result<HashSet<String>> {
+        Ok(VersionManifest::load_all(config)?
+            .into_iter()
+            .flat_map(|(_, _, manifest)| manifest.files.into_values().map(|x| x.hash))
+            .collect())
+    }
+}
+
+/// What rumm last wrote into the Rumble folder, so unchanged files can be skipped without hashing them again.
+/// Stored next to the mod options as `deployed.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeploymentRecord {
+    files: HashMap<PathBuf, DeployedFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeployedFile {
+    pub hash: String,
+    pub size: u64,
+    /// Modified time (seconds since the unix epoch) right after we wrote the file
+    pub modified: u64,
+}
+
+impl DeploymentRecord {
+    fn path(config: &Config) -> PathBuf {
+        config.config_file.with_file_name("deployed.json")
+    }
+
+    pub fn load(config: &Config) -> Self {
+        fs::read_to_string(Self::path(config))
+            .ok()
+            .and_then(|x| serde_json::from_str(&x).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, config: &Config) -> Result<()> {
+        fs::write(Self::path(config), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// True if `destination` is still exactly the file we deployed with this hash.
+    /// Only checks size and modified time, so it never has to read the file
+    pub fn is_up_to_date(&self, destination: &Path, hash: &str) -> bool {
+        let Some(deployed) = self.files.get(destination) else {
+            return false;
+        };
+        deployed.hash == hash && get_size_and_modified(destination).is_some_and(|(size, modified)| {
+            size == deployed.size && modified == deployed.modified
+        })
+    }
+
+    /// Remembers that `destination` now holds the file with this hash
+    pub fn record(&mut self, destination: &Path, hash: &str) -> Result<()> {
+        let (size, modified) = get_size_and_modified(destination)
+            .ok_or(eyre!("Could not read deployed file {:?}", destination))?;
+        self.files.insert(
+            destination.to_path_buf(),
+            DeployedFile {
+                hash: hash.to_string(),
+                size,
+                modified,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn forget(&mut self, destination: &Path) {
+        self.files.remove(destination);
+    }
+}
+
+fn get_size_and_modified(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_secs()))
+}
+
+/// Every regular file under `dir`, recursively
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            files.extend(list_files(&entry.path())?);
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(files)
+}
+
+/// Turns `[dir]/Mods/Some.dll` into `Mods/Some.dll`
+fn relative_key(dir: &Path, path: &Path) -> Result<String> {
+    let relative = path.strip_prefix(dir)?;
+    let parts = relative
+        .components()
+        .map(|x| {
+            x.as_os_str()
+                .to_str()
+                .ok_or(eyre!("file name is not valid UTF‑8: {:?}", x))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(parts.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn identical_files_share_one_blob() -> Result<()> {
+        let dir = TestDir::new("blob");
+        let root = &dir.path;
+        let store = BlobStore {
+            root: root.join(BLOB_DIR_NAME),
+        };
+        for version in ["1.0.0", "1.0.1"] {
+            fs::create_dir_all(root.join(version).join("Mods"))?;
+            fs::write(root.join(version).join("Mods").join("Shared.dll"), b"same bytes")?;
+        }
+        let first = store.ingest_directory(&root.join("1.0.0"))?;
+        let second = store.ingest_directory(&root.join("1.0.1"))?;
+
+        assert_eq!(first, second);
+        assert_eq!(list_files(&store.root)?.len(), 1);
+        // the version directories still look exactly like before
+        assert_eq!(
+            fs::read(root.join("1.0.1").join("Mods").join("Shared.dll"))?,
+            b"same bytes"
+        );
+        // nothing references the blob anymore, so it gets cleaned up
+        assert_eq!(store.remove_unreferenced(&HashSet::new())?, 10);
+        Ok(())
+    }
+}
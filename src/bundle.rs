@@ -0,0 +1,341 @@
+// Offline bundles: a single zip with everything needed to install a set of mods on a PC with no internet.
+// Layout of the zip:
+//   bundle.json                                   - `BundleInfo`, including the mod options (the profile)
+//   thunderstore-mods.json                        - snapshot of the Thunderstore index
+//   mods/[Mod ID]/mod_info.json
+//   mods/[Mod ID]/manifests/[version].json
+//   mods/[Mod ID]/versions/[version]/...          - the version's files
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use color_eyre::eyre::{Context, Result, eyre};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+
+use crate::blob_store::VersionManifest;
+use crate::config_and_such::{Config, ModOptions};
+use crate::mod_cache::ModCache;
+use crate::thunderstore::ModList;
+
+/// Bumped whenever the layout changes in a way older versions of rumm can't read
+const BUNDLE_FORMAT_VERSION: u32 = 1;
+const BUNDLE_INFO_NAME: &str = "bundle.json";
+const BUNDLE_INDEX_NAME: &str = "thunderstore-mods.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleInfo {
+    format_version: u32,
+    /// Which versions of which mods are in the bundle
+    versions: Vec<(Uuid, String)>,
+    /// Options for every mod in the bundle, so they come out enabled and on the same versions
+    options: Vec<ModOptions>,
+}
+
+/// What ended up in (or came out of) a bundle
+#[derive(Debug, Clone, Default)]
+pub struct BundleSummary {
+    pub mods: usize,
+    pub versions: usize,
+}
+
+impl std::fmt::Display for BundleSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} mods ({} versions)", self.mods, self.versions)
+    }
+}
+
+/// Writes a bundle to `destination`. If `all_versions` is false, only the selected version of each mod is included
+pub fn export_bundle(
+    config: &Config,
+    cache: &ModCache,
+    all_versions: bool,
+    destination: &Path,
+) -> Result<BundleSummary> {
+    let options = cache.options().snapshot();
+    let mut versions = vec![];
+    for cached_mod in &cache.cache_mod_list {
+        if all_versions {
+            let cached_mod = cache.prune_extra_versions_from_mod(config, cached_mod)?;
+            versions.extend(
+                cached_mod
+                    .versions
+                    .iter()
+                    .map(|x| (cached_mod.uuid, x.version_number.clone())),
+            );
+        } else if let Some(mod_options) = options.get_mod_options(cached_mod.uuid.to_string()) {
+            versions.push((cached_mod.uuid, mod_options.version.clone()));
+        }
+    }
+    let info = BundleInfo {
+        format_version: BUNDLE_FORMAT_VERSION,
+        options: options
+            .get_all_mod_options()
+            .iter()
+            .filter(|x| versions.iter().any(|(id, _)| id.to_string() == x.id))
+            .cloned()
+            .collect(),
+        versions,
+    };
+
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = fs::File::create(destination)
+        .wrap_err_with(|| format!("Could not create bundle at {:?}", destination))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let zip_options = SimpleFileOptions::default();
+
+    zip.start_file(BUNDLE_INFO_NAME, zip_options)?;
+    zip.write_all(serde_json::to_string_pretty(&info)?.as_bytes())?;
+    zip.start_file(BUNDLE_INDEX_NAME, zip_options)?;
+    zip.write_all(&serde_json::to_vec(cache.get_thunderstore_mod_list().as_ref())?)?;
+
+    let mut written_mods = vec![];
+    for (mod_id, version) in &info.versions {
+        let mod_dir = config.mod_cache_directory.join(mod_id.to_string());
+        if !written_mods.contains(mod_id) {
+            zip.start_file(format!("mods/{mod_id}/mod_info.json"), zip_options)?;
+            zip.write_all(&fs::read(mod_dir.join("mod_info.json"))?)?;
+            written_mods.push(*mod_id);
+        }
+        let manifest = ModCache::ensure_version_manifest(config, mod_id, version)?;
+        zip.start_file(format!("mods/{mod_id}/manifests/{version}.json"), zip_options)?;
+        zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+        let version_dir = mod_dir.join("versions").join(version);
+        for file_name in manifest.files.keys() {
+            zip.start_file(format!("mods/{mod_id}/versions/{version}/{file_name}"), zip_options)?;
+            zip.write_all(&fs::read(version_dir.join(file_name))?)?;
+        }
+    }
+    zip.finish()?;
+    Ok(BundleSummary {
+        mods: written_mods.len(),
+        versions: info.versions.len(),
+    })
+}
+
+/// Puts everything in a bundle into the cache, merges its index into the saved Thunderstore list,
+/// and applies its mod options. Versions that are already cached are left as they are
+pub fn import_bundle(config: &Config, cache: &mut ModCache, source: &Path) -> Result<BundleSummary> {
+    let (info, bundle_mod_list, imported) = unpack_bundle(config, source)?;
+
+    // merge the index so the mods can be resolved without contacting Thunderstore
+    let mut mod_list = ModList::new(config.mod_list_file.clone())?;
+    mod_list.merge(bundle_mod_list);
+    if let Some(parent) = config.mod_list_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&config.mod_list_file, serde_json::to_vec(&mod_list)?)?;
+    cache.set_thunderstore_mod_list(mod_list);
+
+    cache.options().update(|options| {
+        for mod_options in &info.options {
+            options.set_mod_options(mod_options.clone());
+        }
+    })?;
+    cache.update_self_from_cache_in(config)?;
+
+    let mut mods: Vec<Uuid> = info.versions.iter().map(|(id, _)| *id).collect();
+    mods.dedup();
+    Ok(BundleSummary {
+        mods: mods.len(),
+        versions: imported,
+    })
+}
+
+/// Copies the bundle's versions into the cache directory and the blob store, checking every file against the
+/// bundled manifest. Returns what the bundle says about itself, its Thunderstore index, and how many versions
+/// weren't cached yet
+fn unpack_bundle(config: &Config, source: &Path) -> Result<(BundleInfo, ModList, usize)> {
+    let file = fs::File::open(source)
+        .wrap_err_with(|| format!("Could not open bundle at {:?}", source))?;
+    let mut archive = zip::ZipArchive::new(file)?;
+
+    let info: BundleInfo = serde_json::from_str(&read_entry_to_string(&mut archive, BUNDLE_INFO_NAME)?)?;
+    if info.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(eyre!(
+            "This bundle was made by a newer version of rumm (format {}), please update",
+            info.format_version
+        ));
+    }
+
+    let bundle_mod_list: ModList =
+        serde_json::from_str(&read_entry_to_string(&mut archive, BUNDLE_INDEX_NAME)?)?;
+
+    // anything that's already cached could be hardlinked into the blob store, so never write over it
+    let already_cached: Vec<(Uuid, String)> = info
+        .versions
+        .iter()
+        .filter(|(id, version)| VersionManifest::load(config, id, version).is_some())
+        .cloned()
+        .collect();
+    let mut bundled_manifests: HashMap<(Uuid, String), VersionManifest> = HashMap::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let Some(path) = entry.enclosed_name() else {
+            continue;
+        };
+        let Ok(relative) = path.strip_prefix("mods") else {
+            continue;
+        };
+        let parts: Vec<String> = relative
+            .components()
+            .map(|x| x.as_os_str().to_string_lossy().to_string())
+            .collect();
+        let (mod_id, kind, version) = match parts.as_slice() {
+            [_, file_name] if file_name == "mod_info.json" => {
+                let destination = config.mod_cache_directory.join(relative);
+                fs::create_dir_all(destination.parent().ok_or(eyre!("bad path in bundle"))?)?;
+                std::io::copy(&mut entry, &mut fs::File::create(destination)?)?;
+                continue;
+            }
+            [mod_id, kind, version, ..] => (
+                Uuid::parse_str(mod_id)?,
+                kind.clone(),
+                version.trim_end_matches(".json").to_string(),
+            ),
+            _ => continue,
+        };
+        if already_cached.contains(&(mod_id, version.clone())) || !entry.is_file() {
+            continue;
+        }
+        match kind.as_str() {
+            "manifests" => {
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents)?;
+                bundled_manifests.insert((mod_id, version), serde_json::from_str(&contents)?);
+            }
+            "versions" => {
+                let destination = config.mod_cache_directory.join(relative);
+                fs::create_dir_all(destination.parent().ok_or(eyre!("bad path in bundle"))?)?;
+                std::io::copy(&mut entry, &mut fs::File::create(destination)?)?;
+            }
+            _ => {}
+        }
+    }
+
+    let mut imported = 0;
+    for (mod_id, version) in &info.versions {
+        if already_cached.contains(&(*mod_id, version.clone())) {
+            continue;
+        }
+        let bundled = bundled_manifests
+            .remove(&(*mod_id, version.clone()))
+            .ok_or(eyre!("The bundle has no manifest for {mod_id} {version}"))?;
+        let stored = ModCache::store_version_files(config, mod_id, version, bundled.archive_hash.clone())?;
+        if stored.files != bundled.files {
+            // don't leave a damaged copy behind for verify to treat as the real thing
+            let _ = fs::remove_dir_all(config.mod_cache_directory.join(mod_id.to_string()).join("versions").join(version));
+            let _ = fs::remove_file(VersionManifest::path(config, mod_id, version));
+            ModCache::remove_unused_blobs(config)?;
+            let damaged: Vec<&String> = bundled
+                .files
+                .iter()
+                .filter(|(key, file)| stored.files.get(*key) != Some(file))
+                .map(|(key, _)| key)
+                .collect();
+            return Err(eyre!(
+                "{mod_id} {version} in the bundle is damaged, these files don't match: {}",
+                damaged.iter().map(|x| x.as_str()).collect::<Vec<_>>().join(", ")
+            ));
+        }
+        imported += 1;
+    }
+    Ok((info, bundle_mod_list, imported))
+}
+
+fn read_entry_to_string(archive: &mut zip::ZipArchive<fs::File>, name: &str) -> Result<String> {
+    let mut entry = archive
+        .by_name(name)
+        .wrap_err_with(|| format!("{name} is missing, this might not be a rumm bundle"))?;
+    let mut contents = String::new();
+    entry.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_and_such::ModOptionsStore;
+    use crate::test_dir::TestDir;
+    use crate::thunderstore::Mod;
+
+    /// Caches Owner-Thing 1.0.0 in `source`, with `dll` as the contents of Thing.dll
+    fn cache_thing(source: &TestDir, dll: &[u8]) -> Result<(Mod, VersionManifest)> {
+        let config = source.config();
+        let m = Mod::for_test("Owner-Thing", &[("1.0.0", &[])]);
+        let mod_dir = config.mod_cache_directory.join(m.uuid.to_string());
+        fs::create_dir_all(mod_dir.join("versions").join("1.0.0").join("Mods"))?;
+        fs::write(mod_dir.join("mod_info.json"), serde_json::to_string(&m)?)?;
+        fs::write(mod_dir.join("versions").join("1.0.0").join("Mods").join("Thing.dll"), dll)?;
+        let manifest = ModCache::store_version_files(&config, &m.uuid, "1.0.0", Some("zip hash".to_string()))?;
+        Ok((m, manifest))
+    }
+
+    fn export_thing(source: &TestDir, m: &Mod) -> Result<std::path::PathBuf> {
+        let config = source.config();
+        let options = ModOptionsStore::new(&config)?;
+        options.update(|options| options.enable_mod(m))?;
+        let cache = ModCache::for_test(vec![m.clone()], options);
+        let bundle = source.path.join("bundle.zip");
+        let summary = export_bundle(&config, &cache, false, &bundle)?;
+        assert_eq!((summary.mods, summary.versions), (1, 1));
+        Ok(bundle)
+    }
+
+    #[test]
+    fn bundles_round_trip() -> Result<()> {
+        let source = TestDir::new("bundle-source");
+        let (m, manifest) = cache_thing(&source, b"dll")?;
+        let bundle = export_thing(&source, &m)?;
+
+        let destination = TestDir::new("bundle-destination");
+        let destination_config = destination.config();
+        let (info, index, imported) = unpack_bundle(&destination_config, &bundle)?;
+        assert_eq!(imported, 1);
+        assert!(index.mods.iter().any(|x| x.uuid == m.uuid));
+        assert_eq!(info.versions, [(m.uuid, "1.0.0".to_string())]);
+        assert!(info.options.iter().any(|x| x.id == m.uuid.to_string() && x.enabled && x.version == "1.0.0"));
+        // same files, and the original archive hash survives so verify can still redownload it
+        let unpacked = VersionManifest::load(&destination_config, &m.uuid, "1.0.0").unwrap();
+        assert_eq!(unpacked.files, manifest.files);
+        assert_eq!(unpacked.archive_hash.as_deref(), Some("zip hash"));
+        let dll = destination_config
+            .mod_cache_directory
+            .join(m.uuid.to_string())
+            .join("versions")
+            .join("1.0.0")
+            .join("Mods")
+            .join("Thing.dll");
+        assert_eq!(fs::read(dll)?, b"dll");
+        assert!(ModCache::get_mod_from_dir_in_cache(&destination_config.mod_cache_directory.join(m.uuid.to_string())).is_ok());
+
+        // importing it again skips the cached version
+        let (_, _, imported) = unpack_bundle(&destination_config, &bundle)?;
+        assert_eq!(imported, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn damaged_bundles_are_refused() -> Result<()> {
+        let source = TestDir::new("bundle-damaged-source");
+        let (m, _) = cache_thing(&source, b"dll")?;
+        // the manifest still has the old hash, so the bundle carries a file that doesn't match it
+        let dll = source.config().mod_cache_directory.join(m.uuid.to_string()).join("versions/1.0.0/Mods/Thing.dll");
+        fs::write(dll, b"not the dll")?;
+        let bundle = export_thing(&source, &m)?;
+
+        let destination = TestDir::new("bundle-damaged-destination");
+        let destination_config = destination.config();
+        let err = unpack_bundle(&destination_config, &bundle).unwrap_err();
+        assert!(err.to_string().contains("Mods/Thing.dll"), "{err}");
+        // nothing is left behind to be mistaken for a good copy
+        assert!(VersionManifest::load(&destination_config, &m.uuid, "1.0.0").is_none());
+        assert!(!destination_config.mod_cache_directory.join(m.uuid.to_string()).join("versions/1.0.0").exists());
+        Ok(())
+    }
+}
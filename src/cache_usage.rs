@@ -0,0 +1,397 @@
+// Disk usage accounting and cleanup (garbage collection) for the mod cache
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use color_eyre::eyre::{Result, eyre};
+use uuid::Uuid;
+
+use crate::blob_store::{BlobStore, VersionManifest};
+use crate::config_and_such::{Config, RetentionPolicy};
+use crate::lockfile::get_locked_versions;
+use crate::mod_cache::ModCache;
+use crate::thunderstore::{Mod, Version};
+
+/// Name of the file in `[Cache Dir]/[Mod ID]` that remembers when each version was last synced to Rumble
+const USAGE_FILE_NAME: &str = "usage.json";
+
+/// How much space a cached mod takes up, in bytes
+#[derive(Debug, Clone, Default)]
+pub struct ModDiskUsage {
+    /// Actual space used. Files shared between versions are only counted once
+    pub total: u64,
+    /// Size of each cached version, keyed by version number
+    pub versions: HashMap<String, u64>,
+}
+
+/// Why a version was picked to be removed
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvictionReason {
+    /// More versions are cached than `keep_previous_versions` allows
+    TooManyVersions,
+    /// Hasn't been synced to Rumble in this many days
+    Unused(u64),
+}
+
+impl std::fmt::Display for EvictionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvictionReason::TooManyVersions => write!(f, "too many versions kept"),
+            EvictionReason::Unused(days) => write!(f, "unused for {days} days"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GarbageCollectionEntry {
+    pub mod_id: Uuid,
+    pub mod_name: String,
+    pub version: Version,
+    pub bytes: u64,
+    pub reason: EvictionReason,
+}
+
+/// A preview of what a cache cleanup will remove. Nothing is deleted until it is passed to `collect_garbage`
+#[derive(Debug, Clone, Default)]
+pub struct GarbageCollectionPlan {
+    pub entries: Vec<GarbageCollectionEntry>,
+    /// Space that will actually be freed. Can be less than the sum of the entries, since
+    /// files that are shared with versions that are kept stay on disk
+    pub freed_bytes: u64,
+}
+
+impl GarbageCollectionPlan {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Total size of every file under `path`. Symlinks are not followed
+pub fn directory_size(path: &Path) -> Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        total += directory_size(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+/// Formats a byte count for display, like `12.3 MB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+impl ModCache {
+    /// Measures the size of every cached version of a mod
+    pub fn get_mod_disk_usage(&self, config: &Config, mod_to_measure: &Mod) -> Result<ModDiskUsage> {
+        let versions_dir = config
+            .mod_cache_directory
+            .join(mod_to_measure.uuid.to_string())
+            .join("versions");
+        let mut usage = ModDiskUsage::default();
+        // blobs are shared, so count each one once
+        let mut blob_sizes = HashMap::new();
+        for entry in fs::read_dir(&versions_dir)? {
+            let entry = entry?;
+            let version_name = entry
+                .file_name()
+                .into_string()
+                .map_err(|bad| eyre!("file name is not valid UTF‑8: {:?}", bad))?;
+            let size = match VersionManifest::load(config, &mod_to_measure.uuid, &version_name) {
+                Some(manifest) => {
+                    blob_sizes.extend(manifest.files.values().map(|x| (x.hash.clone(), x.size)));
+                    manifest.total_size()
+                }
+                // not in the blob store yet, so the files really are this big
+                None => {
+                    let size = directory_size(&entry.path())?;
+                    usage.total += size;
+                    size
+                }
+            };
+            usage.versions.insert(version_name, size);
+        }
+        usage.total += blob_sizes.values().sum::<u64>();
+        Ok(usage)
+    }
+
+    /// Remembers that a version was just used, so the "unused for X days" rule leaves it alone
+    pub fn record_version_use(config: &Config, mod_id: &Uuid, version: &str) -> Result<()> {
+        let usage_path = config
+            .mod_cache_directory
+            .join(mod_id.to_string())
+            .join(USAGE_FILE_NAME);
+        let mut usage = read_usage_file(&usage_path);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        usage.insert(version.to_string(), now);
+        fs::write(usage_path, serde_json::to_string_pretty(&usage)?)?;
+        Ok(())
+    }
+
+    /// Versions that must never be removed: the selected version of every mod, and anything in the lockfile
+    fn get_protected_versions(&self, config: &Config) -> HashSet<(Uuid, String)> {
+        let mut protected: HashSet<(Uuid, String)> = self.options().read(|options| {
+            self.cache_mod_list
+                .iter()
+                .filter_map(|m| {
+                    let mod_options = options.get_mod_options(m.uuid.to_string())?;
+                    Some((m.uuid, mod_options.version.clone()))
+                })
+                .collect()
+        });
+        for (full_name, version) in get_locked_versions(config) {
+            if let Some(locked_mod) = self.cache_mod_list.iter().find(|x| x.full_name == full_name) {
+                protected.insert((locked_mod.uuid, version));
+            }
+        }
+        protected
+    }
+
+    /// Works out which cached versions the retention rules would remove, without removing anything
+    pub fn plan_garbage_collection(&self, config: &Config) -> Result<GarbageCollectionPlan> {
+        let policy = &config.cache_retention;
+        let protected = self.get_protected_versions(config);
+        let mut plan = GarbageCollectionPlan::default();
+        for cached_mod in &self.cache_mod_list {
+            let cached_mod = self.prune_extra_versions_from_mod(config, cached_mod)?;
+            let usage = self.get_mod_disk_usage(config, &cached_mod)?;
+            let last_used = read_usage_file(
+                &config
+                    .mod_cache_directory
+                    .join(cached_mod.uuid.to_string())
+                    .join(USAGE_FILE_NAME),
+            );
+            // versions are newest first, so anything past the first N unprotected ones is "too many"
+            let mut kept = 0;
+            for version in &cached_mod.versions {
+                if protected.contains(&(cached_mod.uuid, version.version_number.clone())) {
+                    continue;
+                }
+                kept += 1;
+                // versions that were never synced count as used when they were downloaded
+                let version_last_used = last_used
+                    .get(&version.version_number)
+                    .map(|secs| UNIX_EPOCH + Duration::from_secs(*secs))
+                    .or_else(|| {
+                        fs::metadata(
+                            config
+                                .mod_cache_directory
+                                .join(cached_mod.uuid.to_string())
+                                .join("versions")
+                                .join(&version.version_number),
+                        )
+                        .and_then(|x| x.modified())
+                        .ok()
+                    });
+                let Some(reason) = eviction_reason(policy, kept, version_last_used) else {
+                    continue;
+                };
+                plan.entries.push(GarbageCollectionEntry {
+                    mod_id: cached_mod.uuid,
+                    mod_name: cached_mod.name.clone(),
+                    version: version.clone(),
+                    bytes: usage.versions.get(&version.version_number).copied().unwrap_or(0),
+                    reason,
+                });
+            }
+        }
+        plan.freed_bytes = estimate_freed_bytes(config, &plan)?;
+        Ok(plan)
+    }
+
+    /// Removes everything in the plan. Returns the number of bytes actually freed, so shared files that are still
+    /// used by a kept version don't count
+    pub fn collect_garbage(&mut self, config: &Config, plan: &GarbageCollectionPlan) -> Result<u64> {
+        // re-check in case something got selected or locked after the plan was made
+        let protected = self.get_protected_versions(config);
+        let blobs = BlobStore::new(config);
+        let blobs_before = blobs.total_size()?;
+        // versions without a manifest aren't in the blob store, their files are freed right away
+        let mut freed = 0;
+        for entry in &plan.entries {
+            if protected.contains(&(entry.mod_id, entry.version.version_number.clone())) {
+                continue;
+            }
+            let Some(cached_mod) = self.cache_mod_list.iter().find(|x| x.uuid == entry.mod_id).cloned()
+            else {
+                continue;
+            };
+            if VersionManifest::load(config, &entry.mod_id, &entry.version.version_number).is_none() {
+                freed += entry.bytes;
+            }
+            self.remove_version_from_cache(config, &cached_mod, entry.version.clone())?;
+        }
+        // removing a version only drops its links, the space comes back once its blobs are gone
+        Ok(freed + blobs_before.saturating_sub(blobs.total_size()?))
+    }
+}
+
+/// `position` is 1 for the newest unprotected version, 2 for the next, etc.
+fn eviction_reason(
+    policy: &RetentionPolicy,
+    position: usize,
+    last_used: Option<SystemTime>,
+) -> Option<EvictionReason> {
+    if policy.keep_previous_versions.is_some_and(|keep| position > keep) {
+        return Some(EvictionReason::TooManyVersions);
+    }
+    let days = policy.evict_unused_after_days?;
+    let unused_for = SystemTime::now().duration_since(last_used?).unwrap_or_default();
+    (unused_for > Duration::from_secs(days * 24 * 60 * 60)).then_some(EvictionReason::Unused(days))
+}
+
+/// Adds up the blobs that only the versions in the plan use
+fn estimate_freed_bytes(config: &Config, plan: &GarbageCollectionPlan) -> Result<u64> {
+    let removed: HashSet<(Uuid, &str)> = plan
+        .entries
+        .iter()
+        .map(|x| (x.mod_id, x.version.version_number.as_str()))
+        .collect();
+    let mut kept_hashes = HashSet::new();
+    let mut removed_blobs = HashMap::new();
+    let mut removed_with_manifest = HashSet::new();
+    for (mod_id, version, manifest) in VersionManifest::load_all(config)? {
+        if removed.contains(&(mod_id, version.as_str())) {
+            removed_with_manifest.insert((mod_id, version));
+            removed_blobs.extend(manifest.files.into_values().map(|x| (x.hash, x.size)));
+        } else {
+            kept_hashes.extend(manifest.files.into_values().map(|x| x.hash));
+        }
+    }
+    let freed_blobs: u64 = removed_blobs
+        .iter()
+        .filter(|(hash, _)| !kept_hashes.contains(*hash))
+        .map(|(_, size)| size)
+        .sum();
+    // versions without a manifest aren't deduplicated, so all of their space is freed
+    let freed_other: u64 = plan
+        .entries
+        .iter()
+        .filter(|x| !removed_with_manifest.contains(&(x.mod_id, x.version.version_number.clone())))
+        .map(|x| x.bytes)
+        .sum();
+    Ok(freed_blobs + freed_other)
+}
+
+fn read_usage_file(path: &Path) -> HashMap<String, u64> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|x| serde_json::from_str(&x).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_and_such::ModOptionsStore;
+    use crate::lockfile::{LockedMod, Lockfile};
+    use crate::test_dir::TestDir;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    #[test]
+    fn retention_rules() {
+        let keep_two = RetentionPolicy {
+            keep_previous_versions: Some(2),
+            evict_unused_after_days: None,
+        };
+        assert_eq!(eviction_reason(&keep_two, 2, None), None);
+        assert_eq!(eviction_reason(&keep_two, 3, None), Some(EvictionReason::TooManyVersions));
+
+        let thirty_days = RetentionPolicy {
+            keep_previous_versions: None,
+            evict_unused_after_days: Some(30),
+        };
+        let now = SystemTime::now();
+        assert_eq!(eviction_reason(&thirty_days, 9, Some(now - DAY * 29)), None);
+        assert_eq!(eviction_reason(&thirty_days, 9, Some(now - DAY * 31)), Some(EvictionReason::Unused(30)));
+        // no idea when it was used, so it's kept
+        assert_eq!(eviction_reason(&thirty_days, 9, None), None);
+        // both rules off
+        assert_eq!(eviction_reason(&RetentionPolicy::default(), 100, Some(UNIX_EPOCH)), None);
+    }
+
+    #[test]
+    fn selected_and_locked_versions_are_never_planned() -> Result<()> {
+        let dir = TestDir::new("gc");
+        let mut config = dir.config();
+        config.cache_retention.keep_previous_versions = Some(0);
+        let m = Mod::for_test("Owner-Thing", &[("3.0.0", &[]), ("2.0.0", &[]), ("1.0.0", &[])]);
+        for version in ["3.0.0", "2.0.0", "1.0.0"] {
+            let version_dir = config.mod_cache_directory.join(m.uuid.to_string()).join("versions").join(version);
+            fs::create_dir_all(&version_dir)?;
+            fs::write(version_dir.join("Thing.dll"), version)?;
+        }
+        let options = ModOptionsStore::new(&config)?;
+        options.update(|options| {
+            options.enable_mod(&m);
+            options.set_mod_version(&m.uuid, "1.0.0");
+        })?;
+        Lockfile {
+            mods: vec![LockedMod {
+                full_name: m.full_name.clone(),
+                version: "2.0.0".to_string(),
+                download_url: String::new(),
+                archive_hash: String::new(),
+            }],
+        }
+        .save(&Lockfile::default_path(&config))?;
+        let cache = ModCache::for_test(vec![m], options);
+
+        let plan = cache.plan_garbage_collection(&config)?;
+        let planned: Vec<&str> = plan.entries.iter().map(|x| x.version.version_number.as_str()).collect();
+        assert_eq!(planned, ["3.0.0"]);
+        assert_eq!(plan.freed_bytes, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn garbage_collection_frees_only_unshared_files() -> Result<()> {
+        let dir = TestDir::new("collect-garbage");
+        let mut config = dir.config();
+        config.cache_retention.keep_previous_versions = Some(0);
+        let m = Mod::for_test("Owner-Thing", &[("3.0.0", &[]), ("2.0.0", &[]), ("1.0.0", &[])]);
+        let mod_dir = config.mod_cache_directory.join(m.uuid.to_string());
+        for (version, own) in [("3.0.0", "three"), ("2.0.0", "two"), ("1.0.0", "one")] {
+            let version_dir = mod_dir.join("versions").join(version);
+            fs::create_dir_all(&version_dir)?;
+            // every version ships the same library, so it's one blob
+            fs::write(version_dir.join("Shared.dll"), "shared")?;
+            fs::write(version_dir.join("Thing.dll"), own)?;
+            ModCache::store_version_files(&config, &m.uuid, version, None)?;
+        }
+        fs::write(mod_dir.join("mod_info.json"), serde_json::to_string(&m)?)?;
+        let options = ModOptionsStore::new(&config)?;
+        options.update(|options| {
+            options.enable_mod(&m);
+            options.set_mod_version(&m.uuid, "1.0.0");
+        })?;
+        let mut cache = ModCache::for_test(vec![m.clone()], options.clone());
+
+        let plan = cache.plan_garbage_collection(&config)?;
+        assert_eq!(plan.entries.len(), 2);
+        assert_eq!(plan.freed_bytes, ("three".len() + "two".len()) as u64);
+        // picked after the plan was made, so it stays
+        options.update(|options| options.set_mod_version(&m.uuid, "2.0.0"))?;
+        assert_eq!(cache.collect_garbage(&config, &plan)?, "three".len() as u64);
+        assert!(!mod_dir.join("versions").join("3.0.0").exists());
+        assert!(mod_dir.join("versions").join("2.0.0").join("Shared.dll").exists());
+        Ok(())
+    }
+}
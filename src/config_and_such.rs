@@ -4,13 +4,16 @@ use std::{
     env, fs,
     path::{Path, PathBuf},
     str::FromStr,
+    sync::{Arc, Mutex, RwLock, mpsc},
+    time::SystemTime,
 };
 
 use color_eyre::eyre::{Context, Result};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::thunderstore::Mod;
+use crate::thunderstore::{MOD_LIST_CACHE_PATH, Mod};
+use crate::version_requirement::VersionRequirement;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SortType {
@@ -39,6 +42,51 @@ impl From<SortType> for String {
     }
 }
 
+/// Which releases of rumm itself to update to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    /// Stable releases plus prereleases, whichever is newer
+    Prerelease,
+}
+
+/// What "Update All" and the background updater do with a mod
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum UpdatePolicy {
+    /// Updated to the newest version its requirement allows
+    #[default]
+    Auto,
+    /// Shows up under "Updates Available" but is only updated by hand. Good for fragile core libraries
+    NotifyOnly,
+    /// Never checked for updates
+    Manual,
+}
+
+impl UpdatePolicy {
+    pub const ALL: [UpdatePolicy; 3] = [UpdatePolicy::Auto, UpdatePolicy::NotifyOnly, UpdatePolicy::Manual];
+}
+
+impl std::fmt::Display for UpdatePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdatePolicy::Auto => write!(f, "Auto"),
+            UpdatePolicy::NotifyOnly => write!(f, "Notify Only"),
+            UpdatePolicy::Manual => write!(f, "Manual"),
+        }
+    }
+}
+
+/// Rules for which cached versions can be cleaned up. `None` means the rule is off.
+/// Versions that are selected or locked are never removed, no matter the rules
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Keep the selected version plus this many other versions of each mod
+    pub keep_previous_versions: Option<usize>,
+    /// Remove versions that haven't been synced to Rumble in this many days
+    pub evict_unused_after_days: Option<u64>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     /// Will be something like /path/to/common/RUMBLE/
@@ -47,8 +95,38 @@ pub struct Config {
     pub mod_cache_directory: PathBuf,
     /// Will be something like /rumm/config/enabled_mods.json
     pub config_file: PathBuf,
-    pub should_auto_update: bool,
+    /// For mods that don't pick their own `ModOptions::update_policy`
+    #[serde(default)]
+    pub default_update_policy: UpdatePolicy,
     pub thunderstore_browser_sort: SortType,
+    #[serde(default)]
+    pub cache_retention: RetentionPolicy,
+    /// Will be something like /rumm/config/rules.json (see `rules.rs`)
+    #[serde(default = "default_rules_file")]
+    pub rules_file: PathBuf,
+    /// Will be something like /rumm/config/thunderstore-mods.json, the latest response from Thunderstore
+    #[serde(default = "default_mod_list_file")]
+    pub mod_list_file: PathBuf,
+    #[serde(default)]
+    pub update_channel: UpdateChannel,
+    /// Where rumm looks for new releases of itself. Anything that serves the GitHub releases JSON works (see `updater.rs`)
+    #[serde(default = "default_release_source")]
+    pub release_source: String,
+    /// Only read from config files written before update policies, `false` becomes `UpdatePolicy::Manual` on load
+    #[serde(default, rename = "should_auto_update", skip_serializing)]
+    pub legacy_should_auto_update: Option<bool>,
+}
+
+fn default_release_source() -> String {
+    "https://api.github.com/repos/michaelgoldenn/rumm/releases".to_string()
+}
+
+fn default_rules_file() -> PathBuf {
+    Path::new("config").join("rules.json")
+}
+
+fn default_mod_list_file() -> PathBuf {
+    PathBuf::from(MOD_LIST_CACHE_PATH)
 }
 
 impl Config {
@@ -56,20 +134,41 @@ impl Config {
     const CONFIG_PATH: &'static str = "config/config.json";
 
     pub fn new() -> Self {
-        // Default configuration paths
-        let base_dir = Path::new("config");
-
         let mut config = Self {
             rumble_directory: Config::get_rumble_directory(),
-            mod_cache_directory: base_dir.join("mod_cache"),
-            config_file: base_dir.join("enabled_mods.json"),
-            should_auto_update: true,
-            thunderstore_browser_sort: SortType::UpdateDate,
+            ..Config::in_directory(Path::new("config"))
         };
         config.load_from_file(); // ignoring errors, if there is an error it should just use the defaults
         config
     }
 
+    /// The defaults, with every path inside `base_dir` (Rumble too, as `[base_dir]/RUMBLE`).
+    /// Doesn't read `config.json` or go looking for Rumble
+    pub fn in_directory(base_dir: &Path) -> Self {
+        Self {
+            rumble_directory: base_dir.join("RUMBLE"),
+            mod_cache_directory: base_dir.join("mod_cache"),
+            config_file: base_dir.join("enabled_mods.json"),
+            default_update_policy: UpdatePolicy::default(),
+            thunderstore_browser_sort: SortType::UpdateDate,
+            cache_retention: RetentionPolicy::default(),
+            rules_file: base_dir.join("rules.json"),
+            mod_list_file: base_dir.join("thunderstore-mods.json"),
+            update_channel: UpdateChannel::default(),
+            release_source: default_release_source(),
+            legacy_should_auto_update: None,
+        }
+    }
+
+    /// Where MelonLoader looks for mod DLLs
+    pub fn rumble_mods_directory(&self) -> PathBuf {
+        self.rumble_directory.join("Mods")
+    }
+
+    pub fn rumble_user_data_directory(&self) -> PathBuf {
+        self.rumble_directory.join("UserData")
+    }
+
     pub fn save_to_file(&self) -> Result<()> {
         // Ensure the config directory exists
         fs::create_dir_all(Path::new(Self::CONFIG_PATH).parent().unwrap())?;
@@ -83,10 +182,20 @@ impl Config {
     pub fn load_from_file(&mut self) -> Result<()> {
         // Read and deserialize the configuration
         let contents = fs::read_to_string(Self::CONFIG_PATH)?;
-        *self = serde_json::from_str(&contents)?;
+        *self = Config::from_json(&contents)?;
         Ok(())
     }
 
+    /// Parses `config.json`, moving over settings from older versions
+    fn from_json(contents: &str) -> Result<Self> {
+        let mut config: Config = serde_json::from_str(contents)?;
+        // turning off auto updates used to mean never updating on its own
+        if config.legacy_should_auto_update.take() == Some(false) {
+            config.default_update_policy = UpdatePolicy::Manual;
+        }
+        Ok(config)
+    }
+
     // These functions were inspired by / adapted from xLoadingx's work
     // https://github.com/xLoadingx/Rumble-Mod-Manager/blob/05d827240f4a5535c243954da86b731dceec1231/Rumble%20Mod%20Manager/LaunchPage.cs#L860
     // with modifications to account for linux use
@@ -172,8 +281,24 @@ impl Config {
 pub struct ModOptions {
     pub id: String,
     pub version: String,
-    pub version_lock: bool,
+    /// Which versions updates can move to
+    #[serde(default)]
+    pub version_requirement: VersionRequirement,
     pub enabled: bool,
+    /// Installed because another mod needed it, rather than picked by the user (see `dependencies.rs`)
+    #[serde(default)]
+    pub auto_installed: bool,
+    /// `None` follows `Config::default_update_policy`
+    #[serde(default)]
+    pub update_policy: Option<UpdatePolicy>,
+    /// Only read from options files written before version requirements, it becomes an exact requirement on load
+    #[serde(default, rename = "version_lock", skip_serializing)]
+    pub legacy_version_lock: bool,
+}
+impl ModOptions {
+    pub fn resolved_update_policy(&self, config: &Config) -> UpdatePolicy {
+        self.update_policy.unwrap_or(config.default_update_policy)
+    }
 }
 impl PartialEq for ModOptions {
     fn eq(&self, other: &Self) -> bool {
@@ -182,42 +307,38 @@ impl PartialEq for ModOptions {
 }
 
 /// Stores options for downloaded mods, lets you enable the mod, set the version, etc. -
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// This is only the data, use `ModOptionsStore` to share and persist it.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct LocalModOptions {
     mods: Vec<ModOptions>,
 }
 
 impl LocalModOptions {
-    pub fn new(config: &Config) -> Self {
-        let mut enabled_mods = LocalModOptions { mods: vec![] };
-
-        // Try to load the file, but only create a new one if loading fails
-        // due to the file not existing
-        match enabled_mods.load_from_file(&config.config_file) {
-            Ok(_) => enabled_mods,
+    /// Loads the options from `path`. If the file does not exist yet, an empty one is created
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut enabled_mods = LocalModOptions::default();
+        match enabled_mods.load_from_file(path) {
+            Ok(_) => Ok(enabled_mods),
             Err(e) => {
                 // Check if the error is because the file doesn't exist
-                if e.downcast_ref::<std::io::Error>().map_or(false, |io_err| {
+                if e.downcast_ref::<std::io::Error>().is_some_and(|io_err| {
                     io_err.kind() == std::io::ErrorKind::NotFound
                 }) {
                     // Create the directory and an empty file
-                    if let Some(parent) = config.config_file.parent() {
-                        fs::create_dir_all(parent).wrap_err("Failed to create parent directory");
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent).wrap_err("Failed to create parent directory")?;
                     }
-
-                    // Save an empty file
                     enabled_mods
-                        .save_to_file(&config)
-                        .wrap_err("Failed to create enabled mods file");
+                        .save_to_file(path)
+                        .wrap_err("Failed to create enabled mods file")?;
+                    Ok(enabled_mods)
                 } else {
-                    // For other types of errors, return the original error
-                    println!("Error: {}", e);
+                    Err(e)
                 }
-
-                enabled_mods
             }
         }
     }
+
     fn get_mod(&self, id: Uuid) -> Option<&ModOptions> {
         self.mods.iter().find(|x| x.id == id.to_string())
     }
@@ -228,10 +349,10 @@ impl LocalModOptions {
     pub fn is_mod_enabled(&self, this_mod: &Mod) -> Result<bool> {
         Ok(self
             .get_mod_options(this_mod.uuid.to_string())
-            .map_or(false, |opt| opt.enabled))
+            .is_some_and(|opt| opt.enabled))
     }
 
-    pub fn enable_mod(&mut self, mod_to_enable: &Mod, config: &Config) -> Result<()> {
+    pub fn enable_mod(&mut self, mod_to_enable: &Mod) {
         let mod_id = mod_to_enable.uuid.to_string();
 
         match self.get_mod_options_mut(mod_id.clone()) {
@@ -245,50 +366,58 @@ impl LocalModOptions {
                         .expect("mods should always have at least one version")
                         .version_number
                         .clone(),
-                    version_lock: false,
+                    version_requirement: VersionRequirement::Latest,
                     enabled: true,
+                    auto_installed: false,
+                    update_policy: None,
+                    legacy_version_lock: false,
                 };
                 self.mods.push(mod_options);
             }
         }
-
-        self.save_to_file(&config)?;
-        Ok(())
     }
 
-    pub fn disable_mod(&mut self, mod_to_disable: &Mod, config: &Config) -> Result<()> {
+    pub fn disable_mod(&mut self, mod_to_disable: &Mod) {
         if let Some(mod_option) = self.get_mod_options_mut(mod_to_disable.uuid.to_string()) {
             mod_option.enabled = false;
         }
-        self.save_to_file(&config)?;
-        Ok(())
     }
 
-    pub fn set_mod_enabled(
-        &mut self,
-        mod_to_change: &Mod,
-        config: &Config,
-        enable: bool,
-    ) -> Result<()> {
+    pub fn set_mod_enabled(&mut self, mod_to_change: &Mod, enable: bool) {
         match enable {
-            true => self.enable_mod(mod_to_change, config),
-            false => self.disable_mod(mod_to_change, config),
+            true => self.enable_mod(mod_to_change),
+            false => self.disable_mod(mod_to_change),
         }
     }
 
-    pub fn save_to_file(&self, config: &Config) -> Result<()> {
+    fn save_to_file(&self, path: &Path) -> Result<()> {
         // Serialize the entire LocalModOptions structure
         let contents = serde_json::to_string_pretty(&self)?;
-        fs::write(&config.config_file, contents)?;
+        fs::write(path, contents)?;
         Ok(())
     }
 
     fn load_from_file(&mut self, path: &Path) -> Result<()> {
         let contents = fs::read_to_string(path)?;
         self.mods = serde_json::from_str::<LocalModOptions>(&contents)?.mods;
+        self.migrate_version_locks();
         Ok(())
     }
 
+    /// `version_lock: true` used to mean "never update", which is the same as requiring the current version.
+    /// Versions that can't be pinned stop updating through their update policy instead
+    fn migrate_version_locks(&mut self) {
+        for mod_option in &mut self.mods {
+            if mod_option.legacy_version_lock && mod_option.version_requirement.is_latest() {
+                match VersionRequirement::exact(&mod_option.version) {
+                    Ok(pin) => mod_option.version_requirement = pin,
+                    Err(_) => mod_option.update_policy = Some(UpdatePolicy::Manual),
+                }
+            }
+            mod_option.legacy_version_lock = false;
+        }
+    }
+
     // New helper methods for version management
     pub fn get_mod_options(&self, mod_id: String) -> Option<&ModOptions> {
         self.mods.iter().find(|mod_option| mod_option.id == mod_id)
@@ -298,27 +427,41 @@ impl LocalModOptions {
             .iter_mut()
             .find(|mod_option| mod_option.id == mod_id)
     }
-    pub fn set_mod_version(
-        &mut self,
-        mod_id: &Uuid,
-        version: &String,
-        config: &Config,
-    ) -> Result<()> {
+    pub fn set_mod_version(&mut self, mod_id: &Uuid, version: &str) {
         if let Some(mod_option) = self.get_mod_options_mut(mod_id.to_string()) {
             mod_option.version = version.to_string();
-            self.save_to_file(&config)?;
         }
-        Ok(())
     }
-    pub fn set_version_lock(&mut self, mod_id: &Uuid, locked: bool, config: &Config) -> Result<()> {
+    pub fn set_version_requirement(&mut self, mod_id: &Uuid, requirement: VersionRequirement) {
         if let Some(mod_option) = self.get_mod_options_mut(mod_id.to_string()) {
-            mod_option.version_lock = locked;
-            self.save_to_file(&config)?;
+            mod_option.version_requirement = requirement;
+        }
+    }
+    pub fn set_update_policy(&mut self, mod_id: &Uuid, policy: Option<UpdatePolicy>) {
+        if let Some(mod_option) = self.get_mod_options_mut(mod_id.to_string()) {
+            mod_option.update_policy = policy;
         }
-        Ok(())
     }
-    pub fn get_version_lock(&self, mod_id: &Uuid) -> Option<bool> {
-        Some(self.get_mod_options(mod_id.to_string())?.version_lock)
+    pub fn remove_mod_options(&mut self, mod_id: &Uuid) {
+        self.mods.retain(|x| x.id != mod_id.to_string());
+    }
+    pub fn set_auto_installed(&mut self, mod_id: &Uuid, auto_installed: bool) {
+        if let Some(mod_option) = self.get_mod_options_mut(mod_id.to_string()) {
+            mod_option.auto_installed = auto_installed;
+        }
+    }
+    pub fn get_version_requirement(&self, mod_id: &Uuid) -> Option<&VersionRequirement> {
+        Some(&self.get_mod_options(mod_id.to_string())?.version_requirement)
+    }
+    pub fn get_all_mod_options(&self) -> &Vec<ModOptions> {
+        &self.mods
+    }
+    /// Replaces the options of the mod with the same ID, or adds them if the mod has none yet
+    pub fn set_mod_options(&mut self, new_options: ModOptions) {
+        match self.get_mod_options_mut(new_options.id.clone()) {
+            Some(existing) => *existing = new_options,
+            None => self.mods.push(new_options),
+        }
     }
     pub fn get_enabled_mod_ids(&self) -> Vec<Uuid> {
         return self
@@ -329,3 +472,209 @@ impl LocalModOptions {
             .collect();
     }
 }
+
+/// The single shared copy of `LocalModOptions`.
+/// Loaded once, then every change goes through `update`, which takes an advisory lock on the
+/// options file, merges in whatever another process (like the background updater) wrote, saves, and
+/// notifies subscribers. Cloning the store is cheap and every clone sees the same state.
+#[derive(Debug, Clone)]
+pub struct ModOptionsStore {
+    options: Arc<RwLock<LocalModOptions>>,
+    path: PathBuf,
+    /// What the options file looked like the last time we read or wrote it
+    last_seen: Arc<Mutex<Option<FileStamp>>>,
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<()>>>>,
+}
+
+/// Enough of a file to tell if it changed. Two writes can land in the same modified-time tick, so the contents count too
+#[derive(Debug, Clone, PartialEq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+        let contents = fs::read(path).ok()?;
+        let mut hasher = DefaultHasher::new();
+        contents.hash(&mut hasher);
+        Some(Self {
+            modified: fs::metadata(path).and_then(|x| x.modified()).ok(),
+            len: contents.len() as u64,
+            hash: hasher.finish(),
+        })
+    }
+}
+
+impl ModOptionsStore {
+    pub fn new(config: &Config) -> Result<Self> {
+        let path = config.config_file.clone();
+        let options = LocalModOptions::load(&path)?;
+        Ok(Self {
+            options: Arc::new(RwLock::new(options)),
+            last_seen: Arc::new(Mutex::new(FileStamp::of(&path))),
+            path,
+            subscribers: Arc::new(Mutex::new(vec![])),
+        })
+    }
+
+    /// Runs `f` with the current options. Never touches the disk
+    pub fn read<R>(&self, f: impl FnOnce(&LocalModOptions) -> R) -> R {
+        f(&self.options.read().unwrap())
+    }
+
+    /// Returns a copy of the current options
+    pub fn snapshot(&self) -> LocalModOptions {
+        self.read(|options| options.clone())
+    }
+
+    /// Applies `f` to the options and persists the result.
+    /// The file is locked for the whole read-modify-write so two processes can't overwrite each other
+    pub fn update<R>(&self, f: impl FnOnce(&mut LocalModOptions) -> R) -> Result<R> {
+        let result = {
+            let _lock = self.lock_file()?;
+            let mut options = self.options.write().unwrap();
+            // someone else may have written since we last looked, always start from what's on disk
+            *options = LocalModOptions::load(&self.path)?;
+            let result = f(&mut options);
+            options.save_to_file(&self.path)?;
+            *self.last_seen.lock().unwrap() = FileStamp::of(&self.path);
+            result
+        };
+        self.notify();
+        Ok(result)
+    }
+
+    /// Reloads the options if another process changed the file. Returns `true` if anything was reloaded
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        if !self.has_file_changed() {
+            return Ok(false);
+        }
+        {
+            let _lock = self.lock_file()?;
+            *self.options.write().unwrap() = LocalModOptions::load(&self.path)?;
+            *self.last_seen.lock().unwrap() = FileStamp::of(&self.path);
+        }
+        self.notify();
+        Ok(true)
+    }
+
+    /// Returns a receiver that gets a message every time the options change
+    pub fn subscribe(&self) -> mpsc::Receiver<()> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    fn notify(&self) {
+        // drop anyone who stopped listening
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(()).is_ok());
+    }
+
+    fn has_file_changed(&self) -> bool {
+        FileStamp::of(&self.path) != *self.last_seen.lock().unwrap()
+    }
+
+    /// Takes the advisory lock, which is released when the returned file is dropped.
+    /// Uses a separate `.lock` file so the options file itself can be freely rewritten
+    fn lock_file(&self) -> Result<fs::File> {
+        let lock_path = self.path.with_extension("json.lock");
+        if let Some(parent) = lock_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .wrap_err_with(|| format!("Could not open lock file {:?}", lock_path))?;
+        file.lock()
+            .wrap_err_with(|| format!("Could not lock {:?}", lock_path))?;
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn updates_start_from_what_another_process_wrote() -> Result<()> {
+        let dir = TestDir::new("options");
+        let config = dir.config();
+        let first_mod = Mod::for_test("Owner-First", &[("1.0.0", &[])]);
+        let second_mod = Mod::for_test("Owner-Second", &[("1.0.0", &[])]);
+        // two stores on one file, like the gui and the background updater
+        let gui = ModOptionsStore::new(&config)?;
+        let updater = ModOptionsStore::new(&config)?;
+        let changed = gui.subscribe();
+
+        updater.update(|options| options.enable_mod(&first_mod))?;
+        gui.update(|options| options.enable_mod(&second_mod))?;
+
+        // the gui picked up the updater's change instead of overwriting it
+        let expected = vec![first_mod.uuid, second_mod.uuid];
+        assert_eq!(gui.read(|options| options.get_enabled_mod_ids()), expected);
+        assert_eq!(LocalModOptions::load(&config.config_file)?.get_enabled_mod_ids(), expected);
+        assert_eq!(changed.try_iter().count(), 1);
+
+        // and the updater sees the gui's change once it looks again
+        assert!(updater.reload_if_changed()?);
+        assert_eq!(updater.read(|options| options.get_enabled_mod_ids()), expected);
+        assert!(!updater.reload_if_changed()?);
+        Ok(())
+    }
+
+    #[test]
+    fn auto_update_setting_becomes_an_update_policy() -> Result<()> {
+        let dir = TestDir::new("auto-update");
+        // a config.json from before update policies
+        let old_config = |should_auto_update: bool| -> Result<String> {
+            let mut json = serde_json::to_value(dir.config())?;
+            let fields = json.as_object_mut().unwrap();
+            fields.remove("default_update_policy");
+            fields.insert("should_auto_update".to_string(), should_auto_update.into());
+            Ok(json.to_string())
+        };
+        let config = Config::from_json(&old_config(false)?)?;
+        assert_eq!(config.default_update_policy, UpdatePolicy::Manual);
+        assert!(!serde_json::to_string(&config)?.contains("should_auto_update"));
+        assert_eq!(Config::from_json(&old_config(true)?)?.default_update_policy, UpdatePolicy::Auto);
+        Ok(())
+    }
+
+    #[test]
+    fn version_locks_become_exact_requirements() -> Result<()> {
+        let dir = TestDir::new("version-locks");
+        let path = dir.config().config_file;
+        // written before version requirements existed
+        fs::write(
+            &path,
+            r#"{"mods": [
+                {"id": "locked", "version": "1.2.0", "enabled": true, "version_lock": true},
+                {"id": "weird", "version": "v2-final", "enabled": true, "version_lock": true},
+                {"id": "free", "version": "3.0.0", "enabled": true, "version_lock": false}
+            ]}"#,
+        )?;
+        let options = LocalModOptions::load(&path)?;
+        let get = |id: &str| options.get_mod_options(id.to_string()).unwrap().clone();
+        assert_eq!(get("locked").version_requirement.to_string(), "=1.2.0");
+        assert_eq!(get("locked").update_policy, None);
+        // can't be pinned, but it still mustn't start updating
+        assert!(get("weird").version_requirement.is_latest());
+        assert_eq!(get("weird").update_policy, Some(UpdatePolicy::Manual));
+        assert!(get("free").version_requirement.is_latest());
+
+        options.save_to_file(&path)?;
+        assert!(!fs::read_to_string(&path)?.contains("version_lock"));
+        let reloaded = LocalModOptions::load(&path)?;
+        assert_eq!(reloaded.get_mod_options("locked".to_string()).unwrap().version_requirement.to_string(), "=1.2.0");
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+// Working out which mods need which. Dependencies come from the selected version of each cached mod,
+// or the newest allowed version for mods that aren't cached yet.
+// Mods that only got installed because something needed them are marked `auto_installed` in their options,
+// so they can be cleaned up once nothing needs them anymore
+
+use std::collections::HashSet;
+
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::config_and_such::LocalModOptions;
+use crate::mod_cache::ModCache;
+use crate::thunderstore::Mod;
+
+/// One mod in a dependency tree, see `ModCache::dependency_tree`
+#[derive(Debug, Clone)]
+pub struct DependencyNode {
+    pub mod_id: Uuid,
+    pub name: String,
+    /// The selected version if it's cached, otherwise the one that would be installed. `None` if no version is allowed
+    pub version: Option<String>,
+    pub cached: bool,
+    pub enabled: bool,
+    /// Set when the mod already showed up higher in the same branch, so its children are left out
+    pub cycle: bool,
+    pub children: Vec<DependencyNode>,
+}
+
+/// A mod that needs a library, see `ModCache::installed_dependents`
+#[derive(Debug, Clone)]
+pub struct Dependent {
+    pub dependent: Mod,
+    /// False if it only needs the library through another dependency
+    pub direct: bool,
+    pub enabled: bool,
+}
+
+/// Dependency strings look like `Owner-Name-1.0.0`, this is the `Owner-Name` part
+fn package_name(dependency: &str) -> &str {
+    dependency.rsplit_once('-').map_or(dependency, |(name, _)| name)
+}
+
+impl ModCache {
+    /// The mod and everything under it, with the version each one resolves to
+    pub fn dependency_tree(&self, m: &Mod, options: &LocalModOptions) -> DependencyNode {
+        self.dependency_node(m, options, &mut vec![])
+    }
+
+    /// `branch` is the path from the root, to catch cycles
+    fn dependency_node(&self, m: &Mod, options: &LocalModOptions, branch: &mut Vec<Uuid>) -> DependencyNode {
+        let cached = self.cache_mod_list.iter().any(|x| x.uuid == m.uuid);
+        let selected = options.get_mod_options(m.uuid.to_string());
+        let version = match selected {
+            Some(selected) if cached => Some(selected.version.clone()),
+            _ => self.rules().newest_allowed_version(m).map(|x| x.version_number.clone()),
+        };
+        let cycle = branch.contains(&m.uuid);
+        let mut children = vec![];
+        if !cycle {
+            branch.push(m.uuid);
+            for dependency in self.direct_dependencies(m, options) {
+                children.push(self.dependency_node(dependency, options, branch));
+            }
+            branch.pop();
+        }
+        DependencyNode {
+            mod_id: m.uuid,
+            name: m.name.clone(),
+            version,
+            cached,
+            enabled: selected.is_some_and(|x| x.enabled),
+            cycle,
+            children,
+        }
+    }
+
+    /// Every cached mod that needs `library`, enabled or not
+    pub fn installed_dependents(&self, library: &Uuid, options: &LocalModOptions) -> Vec<Dependent> {
+        self.cache_mod_list
+            .iter()
+            .filter(|m| m.uuid != *library)
+            .filter_map(|m| {
+                let direct = self
+                    .direct_dependencies(m, options)
+                    .iter()
+                    .any(|x| x.uuid == *library);
+                let needed = direct
+                    || self
+                        .dependency_closure(&[m], options)
+                        .iter()
+                        .any(|x| x.uuid == *library);
+                needed.then(|| Dependent {
+                    dependent: m.clone(),
+                    direct,
+                    enabled: options.is_mod_enabled(m).unwrap_or(false),
+                })
+            })
+            .collect()
+    }
+
+    /// Packages on Thunderstore whose newest version lists `library` as a dependency
+    pub fn thunderstore_dependents(&self, library: &Mod) -> Vec<&Mod> {
+        self.get_thunderstore_mod_list()
+            .mods
+            .iter()
+            .filter(|m| {
+                m.versions.first().is_some_and(|x| {
+                    x.dependencies
+                        .iter()
+                        .any(|dependency| package_name(dependency) == library.full_name)
+                })
+            })
+            .collect()
+    }
+
+    /// What this mod's selected version depends on directly. Dependencies that can't be found are only logged
+    pub fn direct_dependencies(&self, m: &Mod, options: &LocalModOptions) -> Vec<&Mod> {
+        let version = options
+            .get_mod_options(m.uuid.to_string())
+            .map(|x| x.version.clone());
+        // the cached `mod_info.json` can be older than the index, so prefer the index's copy
+        let m = self.find_mod(&m.uuid).unwrap_or(m);
+        match self.get_mod_dependencies(m, version.as_ref()) {
+            Ok(dependencies) => dependencies,
+            Err(e) => {
+                debug!("could not get the dependencies of {}: {e}", m.name);
+                vec![]
+            }
+        }
+    }
+
+    /// Everything these mods need, directly or through other dependencies. Leaves out the mods themselves
+    pub fn dependency_closure(&self, mods: &[&Mod], options: &LocalModOptions) -> Vec<&Mod> {
+        let roots: HashSet<Uuid> = mods.iter().map(|x| x.uuid).collect();
+        let mut seen = roots.clone();
+        let mut closure = vec![];
+        let mut to_visit: Vec<&Mod> = mods
+            .iter()
+            .flat_map(|x| self.direct_dependencies(x, options))
+            .collect();
+        while let Some(dependency) = to_visit.pop() {
+            // `seen` also stops dependency cycles
+            if !seen.insert(dependency.uuid) {
+                continue;
+            }
+            to_visit.extend(self.direct_dependencies(dependency, options));
+            closure.push(dependency);
+        }
+        closure
+    }
+
+    /// Enabled cached mods that need any of `targets`, directly or not, and aren't targets themselves
+    pub fn dependents(&self, targets: &[Uuid], options: &LocalModOptions) -> Vec<&Mod> {
+        self.cache_mod_list
+            .iter()
+            .filter(|m| !targets.contains(&m.uuid))
+            .filter(|m| options.is_mod_enabled(m).unwrap_or(false))
+            .filter(|m| {
+                self.dependency_closure(&[m], options)
+                    .iter()
+                    .any(|x| targets.contains(&x.uuid))
+            })
+            .collect()
+    }
+
+    /// Cached mods that were only installed as a dependency, and that no mod the user picked needs anymore
+    pub fn orphaned_dependencies(&self, options: &LocalModOptions) -> Vec<&Mod> {
+        let is_auto_installed = |m: &Mod| {
+            options
+                .get_mod_options(m.uuid.to_string())
+                .is_some_and(|x| x.auto_installed)
+        };
+        // disabled mods count too, their dependencies would just have to come back when they're enabled again
+        let picked: Vec<&Mod> = self.cache_mod_list.iter().filter(|m| !is_auto_installed(m)).collect();
+        let needed: HashSet<Uuid> = self
+            .dependency_closure(&picked, options)
+            .iter()
+            .map(|x| x.uuid)
+            .collect();
+        self.cache_mod_list
+            .iter()
+            .filter(|m| is_auto_installed(m) && !needed.contains(&m.uuid))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use color_eyre::eyre::Result;
+
+    use super::*;
+    use crate::config_and_such::ModOptionsStore;
+    use crate::test_dir::TestDir;
+
+    fn ids(mods: &[&Mod]) -> HashSet<Uuid> {
+        mods.iter().map(|x| x.uuid).collect()
+    }
+
+    #[test]
+    fn closures_handle_diamonds_cycles_and_disabled_mods() -> Result<()> {
+        let dir = TestDir::new("dependencies");
+        let library = Mod::for_test("Owner-Library", &[("1.0.0", &[])]);
+        let left = Mod::for_test("Owner-Left", &[("1.0.0", &["Owner-Library-1.0.0"])]);
+        let right = Mod::for_test("Owner-Right", &[("1.0.0", &["Owner-Library-1.0.0"])]);
+        // needs the library twice over, through left and right
+        let top = Mod::for_test("Owner-Top", &[("1.0.0", &["Owner-Left-1.0.0", "Owner-Right-1.0.0"])]);
+        let ping = Mod::for_test("Owner-Ping", &[("1.0.0", &["Owner-Pong-1.0.0"])]);
+        let pong = Mod::for_test("Owner-Pong", &[("1.0.0", &["Owner-Ping-1.0.0"])]);
+        let leftover = Mod::for_test("Owner-Leftover", &[("1.0.0", &[])]);
+        let all = [&library, &left, &right, &top, &ping, &pong, &leftover];
+        let cache = ModCache::for_test(
+            all.iter().map(|x| (*x).clone()).collect(),
+            ModOptionsStore::new(&dir.config())?,
+        );
+        let mut options = LocalModOptions::default();
+        for m in all {
+            options.enable_mod(m);
+        }
+        for m in [&library, &left, &right, &leftover] {
+            options.set_auto_installed(&m.uuid, true);
+        }
+
+        // every mod shows up once and the roots are left out
+        let closure = cache.dependency_closure(&[&top], &options);
+        assert_eq!(closure.len(), 3);
+        assert_eq!(ids(&closure), ids(&[&left, &right, &library]));
+        assert_eq!(ids(&cache.dependency_closure(&[&ping], &options)), ids(&[&pong]));
+        let tree = cache.dependency_tree(&ping, &options);
+        let back_to_ping = &tree.children[0].children[0];
+        assert_eq!(back_to_ping.mod_id, ping.uuid);
+        assert!(back_to_ping.cycle && back_to_ping.children.is_empty());
+
+        assert_eq!(ids(&cache.dependents(&[library.uuid], &options)), ids(&[&left, &right, &top]));
+        assert_eq!(ids(&cache.orphaned_dependencies(&options)), ids(&[&leftover]));
+
+        // a disabled mod doesn't need anything right now, but its dependencies aren't orphans either
+        options.disable_mod(&top);
+        assert_eq!(ids(&cache.dependents(&[library.uuid], &options)), ids(&[&left, &right]));
+        assert_eq!(ids(&cache.orphaned_dependencies(&options)), ids(&[&leftover]));
+        Ok(())
+    }
+
+    #[test]
+    fn dependency_strings_drop_the_version() {
+        assert_eq!(package_name("LavaGang-MelonLoader-0.6.6"), "LavaGang-MelonLoader");
+        assert_eq!(package_name("UlvakSkillz-RumbleModdingAPI-4.1.2"), "UlvakSkillz-RumbleModdingAPI");
+    }
+}
@@ -0,0 +1,239 @@
+// Drift: differences between what should be deployed to Rumble and what's actually there.
+// The game, other mod managers, or people editing by hand can all change `RUMBLE/Mods` and `RUMBLE/UserData`,
+// so this compares the live folders against the enabled mods' manifests. It only reads, fixing is up to a sync
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{Result, eyre};
+use tracing::debug;
+
+use crate::blob_store::{BlobStore, DeploymentRecord, VersionManifest};
+use crate::config_and_such::Config;
+use crate::mod_cache::ModCache;
+
+/// What's different about the Rumble folder, see `ModCache::check_drift`
+#[derive(Debug, Clone, Default)]
+pub struct DriftReport {
+    /// Files an enabled mod should have deployed that aren't there
+    pub missing: Vec<DriftedFile>,
+    /// Deployed DLLs that don't match the cached copy anymore
+    pub modified: Vec<DriftedFile>,
+    /// DLLs in `Mods` that no enabled mod accounts for
+    pub unknown: Vec<UnknownFile>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriftedFile {
+    pub mod_name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownFile {
+    pub path: PathBuf,
+    /// Set when it belongs to a cached mod that's disabled (or blocked), so it should have been removed
+    pub disabled_mod: Option<String>,
+}
+
+/// One file the Rumble folder should have
+struct ExpectedFile {
+    mod_name: String,
+    path: PathBuf,
+    /// `None` for UserData, which mods and the game edit all the time, so only a missing file counts
+    hash: Option<String>,
+}
+
+impl DriftReport {
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.missing.len() + self.modified.len() + self.unknown.len()
+    }
+}
+
+impl std::fmt::Display for DriftReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} missing, {} modified, {} unknown",
+            self.missing.len(),
+            self.modified.len(),
+            self.unknown.len()
+        )
+    }
+}
+
+impl std::fmt::Display for DriftedFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", file_name(&self.path), self.mod_name)
+    }
+}
+
+impl std::fmt::Display for UnknownFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.disabled_mod {
+            Some(name) => write!(f, "{} ({name} is disabled)", file_name(&self.path)),
+            None => write!(f, "{}", file_name(&self.path)),
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
+
+impl ModCache {
+    /// Compares the Rumble folder with what the enabled mods and their selected versions say should be there.
+    /// Versions without a manifest yet are skipped, they get one on their next sync
+    pub fn check_drift(&self, config: &Config) -> Result<DriftReport> {
+        if !matches!(ModCache::check_for_rumble_exe(&config.rumble_directory), Ok(true)) {
+            return Err(eyre!("Rumble could not be found! Check the settings to make sure your rumble path is correct"));
+        }
+        let mods_dir = config.rumble_mods_directory();
+        let user_data_dir = config.rumble_user_data_directory();
+        let mut expected = vec![];
+        // files of disabled mods, so leftovers can say where they came from
+        let mut disabled_files = HashMap::new();
+        for m in &self.cache_mod_list {
+            let Some(options) = self
+                .options()
+                .read(|options| options.get_mod_options(m.uuid.to_string()).cloned())
+            else {
+                continue;
+            };
+            let Some(manifest) = VersionManifest::load(config, &m.uuid, &options.version) else {
+                debug!("{} {} has no manifest yet, skipping it", m.name, options.version);
+                continue;
+            };
+            let enabled = options.enabled && !self.rules().is_blocked(m);
+            for (key, file) in manifest.files {
+                // only files right inside these folders get synced
+                let path = match key.split_once('/') {
+                    Some(("Mods", name)) if !name.contains('/') => mods_dir.join(name),
+                    Some(("UserData", name)) if !name.contains('/') => user_data_dir.join(name),
+                    _ => continue,
+                };
+                let is_mod_file = path.starts_with(&mods_dir);
+                if !enabled {
+                    if is_mod_file {
+                        disabled_files.insert(path, m.name.clone());
+                    }
+                    continue;
+                }
+                expected.push(ExpectedFile {
+                    mod_name: m.name.clone(),
+                    path,
+                    hash: is_mod_file.then_some(file.hash),
+                });
+            }
+        }
+        compare(&expected, &disabled_files, &mods_dir, &DeploymentRecord::load(config))
+    }
+}
+
+fn compare(
+    expected: &[ExpectedFile],
+    disabled_files: &HashMap<PathBuf, String>,
+    mods_dir: &Path,
+    deployed: &DeploymentRecord,
+) -> Result<DriftReport> {
+    let mut report = DriftReport::default();
+    for file in expected {
+        let drifted = || DriftedFile {
+            mod_name: file.mod_name.clone(),
+            path: file.path.clone(),
+        };
+        if !file.path.is_file() {
+            report.missing.push(drifted());
+            continue;
+        }
+        let Some(hash) = &file.hash else {
+            continue;
+        };
+        // the deployment record knows the files rumm wrote itself, those don't need hashing again
+        if !deployed.is_up_to_date(&file.path, hash) && BlobStore::hash_file(&file.path)? != *hash {
+            report.modified.push(drifted());
+        }
+    }
+    let known: HashSet<&PathBuf> = expected.iter().map(|x| &x.path).collect();
+    if let Ok(entries) = fs::read_dir(mods_dir) {
+        for path in entries.filter_map(|x| Some(x.ok()?.path())) {
+            let is_dll = path
+                .extension()
+                .is_some_and(|x| x.eq_ignore_ascii_case("dll"));
+            if is_dll && path.is_file() && !known.contains(&path) {
+                report.unknown.push(UnknownFile {
+                    disabled_mod: disabled_files.get(&path).cloned(),
+                    path,
+                });
+            }
+        }
+    }
+    report.unknown.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(report)
+}
+
+/// Cheap summary of the Rumble folders (names, sizes, modified times), so a watcher can tell when to check again
+pub fn rumble_fingerprint(config: &Config) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for dir in [config.rumble_mods_directory(), config.rumble_user_data_directory()] {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut files: Vec<(PathBuf, Option<(u64, std::time::SystemTime)>)> = entries
+            .filter_map(|x| x.ok())
+            .map(|x| {
+                let metadata = x.metadata().ok().and_then(|m| Some((m.len(), m.modified().ok()?)));
+                (x.path(), metadata)
+            })
+            .collect();
+        // read_dir doesn't promise an order
+        files.sort();
+        files.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn finds_missing_modified_and_unknown_files() -> Result<()> {
+        let dir = TestDir::new("drift");
+        let mods_dir = dir.path.clone();
+        fs::write(mods_dir.join("Fine.dll"), b"fine")?;
+        fs::write(mods_dir.join("Edited.dll"), b"edited by hand")?;
+        fs::write(mods_dir.join("Stranger.dll"), b"from somewhere else")?;
+        fs::write(mods_dir.join("Leftover.dll"), b"disabled")?;
+        fs::write(mods_dir.join("notes.txt"), b"not a mod")?;
+        let expect = |name: &str, contents: &[u8]| ExpectedFile {
+            mod_name: "Some Mod".to_string(),
+            path: mods_dir.join(name),
+            hash: Some(BlobStore::hash_bytes(contents)),
+        };
+        let expected = [
+            expect("Fine.dll", b"fine"),
+            expect("Edited.dll", b"original"),
+            expect("Gone.dll", b"gone"),
+        ];
+        let disabled = HashMap::from([(mods_dir.join("Leftover.dll"), "Old Mod".to_string())]);
+
+        let report = compare(&expected, &disabled, &mods_dir, &DeploymentRecord::default())?;
+        assert_eq!(report.missing.iter().map(|x| file_name(&x.path)).collect::<Vec<_>>(), ["Gone.dll"]);
+        assert_eq!(report.modified.iter().map(|x| file_name(&x.path)).collect::<Vec<_>>(), ["Edited.dll"]);
+        assert_eq!(
+            report.unknown.iter().map(|x| x.to_string()).collect::<Vec<_>>(),
+            ["Leftover.dll (Old Mod is disabled)", "Stranger.dll"]
+        );
+        Ok(())
+    }
+}
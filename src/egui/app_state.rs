@@ -0,0 +1,362 @@
+// The one copy of everything the tabs show: the Thunderstore index, the mod cache, and the mod options.
+// The worker owns the cache and publishes a new snapshot after every change.
+// Tabs only ever read snapshots, so drawing a frame never waits on a download
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use color_eyre::eyre::Result;
+use eframe::egui;
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{DebounceEventResult, Debouncer, new_debouncer};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::cache_usage::ModDiskUsage;
+use crate::config_and_such::{Config, ModOptionsStore};
+use crate::drift::{self, DriftReport};
+use crate::icon_cache::IconCache;
+use crate::mod_cache::ModCache;
+use crate::rules::IncompatibilityRule;
+use crate::thunderstore::{MOD_LIST_CACHE_PATH, Mod, ModList};
+
+/// How often to check if the saved Thunderstore response was refreshed
+const INDEX_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const CACHE_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+const RUMBLE_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+/// How long the file watchers wait for things to settle, copying a mod in touches a lot of files
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Everything the UI needs for one frame. Never changes once published
+pub struct AppSnapshot {
+    /// Goes up by one every time a new snapshot is published
+    pub revision: u64,
+    /// Copy of the cache as it was when the snapshot was taken. Only read from it, changes go through the worker
+    pub cache: ModCache,
+    /// Every cached mod with only the versions that are actually on disk
+    pub pruned_mods: HashMap<Uuid, Mod>,
+    /// Size of each cached mod. Measured here so the Mods tab doesn't walk the cache while drawing
+    pub disk_usage: HashMap<Uuid, ModDiskUsage>,
+}
+
+impl AppSnapshot {
+    /// Every mod on Thunderstore. The same `Arc` until a new index is set
+    pub fn index(&self) -> &Arc<ModList> {
+        self.cache.get_thunderstore_mod_list()
+    }
+
+    /// Rules that enabling these mods would break, on top of the mods that are enabled now
+    pub fn find_conflicts(&self, options: &ModOptionsStore, enabling: &[&Mod]) -> Vec<IncompatibilityRule> {
+        let enabled: Vec<&Mod> = options.read(|options| {
+            self.pruned_mods
+                .values()
+                .filter(|m| options.get_mod_options(m.uuid.to_string()).is_some_and(|x| x.enabled))
+                .collect()
+        });
+        self.cache
+            .rules()
+            .find_conflicts(enabling, &enabled)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    fn new(revision: u64, cache: &ModCache) -> Self {
+        let config = Config::new();
+        let pruned_mods = cache
+            .cache_mod_list
+            .iter()
+            .filter_map(|m| match cache.prune_extra_versions_from_mod(&config, m) {
+                Ok(pruned) => Some((m.uuid, pruned)),
+                Err(e) => {
+                    warn!("could not read the cached versions of {}: {e}", m.name);
+                    None
+                }
+            })
+            .collect();
+        let disk_usage = cache
+            .cache_mod_list
+            .iter()
+            .filter_map(|m| Some((m.uuid, cache.get_mod_disk_usage(&config, m).ok()?)))
+            .collect();
+        Self {
+            revision,
+            cache: cache.clone(),
+            pruned_mods,
+            disk_usage,
+        }
+    }
+}
+
+/// Shared between the tabs and the worker. Cheap to clone
+#[derive(Clone)]
+pub struct AppState {
+    /// Only the worker writes to this
+    cache: Arc<tokio::sync::RwLock<ModCache>>,
+    snapshot: Arc<RwLock<Arc<AppSnapshot>>>,
+    /// Revision of the latest snapshot
+    revision: Arc<AtomicU64>,
+    options: ModOptionsStore,
+    icons: IconCache,
+    /// `ModCache::cache_fingerprint` as of the last publish, so `watch_cache` skips rumm's own changes
+    cache_fingerprint: Arc<Mutex<u64>>,
+    /// Latest comparison of the Rumble folder with the enabled mods, `None` until the first one finishes
+    drift: Arc<RwLock<Option<Arc<DriftReport>>>>,
+}
+
+impl AppState {
+    pub fn new(index: &ModList, options: ModOptionsStore) -> Self {
+        let cache = ModCache::new(index, options.clone());
+        Self {
+            snapshot: Arc::new(RwLock::new(Arc::new(AppSnapshot::new(0, &cache)))),
+            revision: Arc::new(AtomicU64::new(0)),
+            cache: Arc::new(tokio::sync::RwLock::new(cache)),
+            options,
+            icons: IconCache::new(&Config::new()),
+            cache_fingerprint: Arc::new(Mutex::new(ModCache::cache_fingerprint(&Config::new()))),
+            drift: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// The latest snapshot. Only holds the lock long enough to clone an `Arc`
+    pub fn snapshot(&self) -> Arc<AppSnapshot> {
+        self.snapshot.read().unwrap().clone()
+    }
+
+    pub fn options(&self) -> &ModOptionsStore {
+        &self.options
+    }
+
+    pub fn icons(&self) -> &IconCache {
+        &self.icons
+    }
+
+    pub fn drift(&self) -> Option<Arc<DriftReport>> {
+        self.drift.read().unwrap().clone()
+    }
+
+    pub fn set_drift(&self, report: DriftReport) {
+        *self.drift.write().unwrap() = Some(Arc::new(report));
+    }
+
+    /// Caches any missing icons in the background, installed mods first since they're shown first
+    pub fn fill_icons(&self, handle: &Handle, ctx: egui::Context) {
+        let snapshot = self.snapshot();
+        let mods = snapshot
+            .cache
+            .cache_mod_list
+            .iter()
+            .chain(&snapshot.index().mods)
+            .cloned()
+            .collect();
+        let icons = self.icons.clone();
+        handle.spawn(async move { icons.fill(mods, || ctx.request_repaint()).await });
+    }
+
+    /// Waits for the cache for a command. Call `publish` once the command is done with it
+    pub async fn lock_cache(&self) -> tokio::sync::RwLockWriteGuard<'_, ModCache> {
+        self.cache.write().await
+    }
+
+    /// Rescans the cache from disk and hands the result to every tab
+    pub fn publish(&self, cache: &mut ModCache) -> Result<()> {
+        cache.update_self_from_cache()?;
+        *self.cache_fingerprint.lock().unwrap() = ModCache::cache_fingerprint(&Config::new());
+        // building it walks the cache, so do that before taking the lock the tabs read through
+        let revision = self.revision.fetch_add(1, Ordering::SeqCst) + 1;
+        let snapshot = Arc::new(AppSnapshot::new(revision, cache));
+        *self.snapshot.write().unwrap() = snapshot;
+        debug!("published app state revision {revision}");
+        Ok(())
+    }
+
+    /// Swaps in a newer Thunderstore index
+    pub async fn set_index(&self, index: ModList) -> Result<()> {
+        let mut cache = self.lock_cache().await;
+        cache.set_thunderstore_mod_list(index);
+        self.publish(&mut cache)
+    }
+
+    /// Rescans the cache, which also reloads the rule file
+    pub async fn refresh(&self) -> Result<()> {
+        let mut cache = self.lock_cache().await;
+        self.publish(&mut cache)
+    }
+
+    /// Picks up the hourly Thunderstore refresh (see `main.rs`) and edits to the rule file, and redraws when they land
+    pub fn watch_index(&self, handle: &Handle, ctx: egui::Context) {
+        let state = self.clone();
+        handle.spawn(async move {
+            let path = PathBuf::from(MOD_LIST_CACHE_PATH);
+            let mut last_modified = modified_time(&path);
+            let mut rules_last_modified = modified_time(&Config::new().rules_file);
+            loop {
+                tokio::time::sleep(INDEX_CHECK_INTERVAL).await;
+                // the path can change in Settings, that counts as a change too
+                let rules_modified = modified_time(&Config::new().rules_file);
+                if rules_modified != rules_last_modified {
+                    rules_last_modified = rules_modified;
+                    match state.refresh().await {
+                        Ok(()) => ctx.request_repaint(),
+                        Err(e) => warn!("could not reload the rule file: {e}"),
+                    }
+                }
+                let modified = modified_time(&path);
+                if modified == last_modified {
+                    continue;
+                }
+                let result = match ModList::new(path.clone()) {
+                    Ok(index) => state.set_index(index).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(()) => {
+                        last_modified = modified;
+                        // new mods need icons too
+                        state.fill_icons(&Handle::current(), ctx.clone());
+                        ctx.request_repaint();
+                    }
+                    // probably read it mid-write, try again next time
+                    Err(e) => warn!("could not load the Thunderstore mod list: {e}"),
+                }
+            }
+        });
+    }
+
+    /// Picks up changes made to the cache directory outside rumm (deleting a mod folder by hand, another copy of rumm...).
+    /// Polls a fingerprint of the directory instead if it can't be watched
+    pub fn watch_cache(&self, handle: &Handle, ctx: egui::Context) {
+        let state = self.clone();
+        handle.spawn(async move {
+            let (tx, mut changes) = mpsc::unbounded_channel();
+            if let Some(_watcher) = watch_directories(&[Config::new().mod_cache_directory], tx) {
+                while changes.recv().await.is_some() {
+                    debug!("the cache directory changed, rescanning it");
+                    match state.refresh().await {
+                        Ok(()) => ctx.request_repaint(),
+                        Err(e) => warn!("could not rescan the mod cache: {e}"),
+                    }
+                }
+                return;
+            }
+            loop {
+                tokio::time::sleep(CACHE_CHECK_INTERVAL).await;
+                let fingerprint = ModCache::cache_fingerprint(&Config::new());
+                if fingerprint == *state.cache_fingerprint.lock().unwrap() {
+                    continue;
+                }
+                debug!("the cache directory changed, rescanning it");
+                match state.refresh().await {
+                    Ok(()) => ctx.request_repaint(),
+                    Err(e) => {
+                        warn!("could not rescan the mod cache: {e}");
+                        // don't retry every tick, wait for it to change again
+                        *state.cache_fingerprint.lock().unwrap() = fingerprint;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Checks the Rumble folder for drift whenever it, the cache, or the mod options change.
+    /// Mods and UserData are watched, or polled with a fingerprint if they can't be (Rumble not found, say)
+    pub fn watch_rumble(&self, handle: &Handle, ctx: egui::Context) {
+        let state = self.clone();
+        let (tx, mut changes) = mpsc::unbounded_channel();
+        // the options store only hands out blocking receivers
+        {
+            let options_changed = self.options.subscribe();
+            let tx = tx.clone();
+            std::thread::spawn(move || while options_changed.recv().is_ok() && tx.send(()).is_ok() {});
+        }
+        handle.spawn(async move {
+            let mut watched = vec![];
+            let mut watcher = None;
+            let mut last_checked = None;
+            let mut changed = true;
+            loop {
+                let snapshot = state.snapshot();
+                let config = Config::new();
+                // the Rumble folder can be moved in Settings
+                let directories = vec![config.rumble_mods_directory(), config.rumble_user_data_directory()];
+                if directories != watched {
+                    watcher = watch_directories(&directories, tx.clone());
+                    watched = directories;
+                    changed = true;
+                }
+                let fingerprint = watcher.is_none().then(|| drift::rumble_fingerprint(&config));
+                let key = (fingerprint, snapshot.revision);
+                if changed || last_checked != Some(key) {
+                    last_checked = Some(key);
+                    // hashing DLLs is blocking work
+                    let result = tokio::task::spawn_blocking(move || snapshot.cache.check_drift(&config)).await;
+                    match result {
+                        Ok(Ok(report)) => {
+                            if !report.is_empty() {
+                                debug!("the Rumble folder drifted: {report}");
+                            }
+                            state.set_drift(report);
+                            ctx.request_repaint();
+                        }
+                        // usually just Rumble not being found, Settings already says so
+                        Ok(Err(e)) => debug!("could not check the Rumble folder: {e}"),
+                        Err(e) => warn!("drift check panicked: {e}"),
+                    }
+                }
+                // folder and option changes wake it right away, new snapshots are only noticed on the interval
+                changed = matches!(tokio::time::timeout(RUMBLE_CHECK_INTERVAL, changes.recv()).await, Ok(Some(())));
+                while changes.try_recv().is_ok() {}
+            }
+        });
+    }
+}
+
+/// Sends to `tx` once things settle after anything under the directories changes. Stops when the watcher is dropped.
+/// None if the watcher couldn't start, like when a directory doesn't exist
+fn watch_directories(paths: &[PathBuf], tx: mpsc::UnboundedSender<()>) -> Option<Debouncer<RecommendedWatcher>> {
+    let result = new_debouncer(WATCH_DEBOUNCE, move |events: DebounceEventResult| match events {
+        Ok(_) => {
+            let _ = tx.send(());
+        }
+        Err(e) => warn!("file watcher error: {e}"),
+    })
+    .and_then(|mut debouncer| {
+        for path in paths {
+            debouncer.watcher().watch(path, RecursiveMode::Recursive)?;
+        }
+        Ok(debouncer)
+    });
+    match result {
+        Ok(debouncer) => Some(debouncer),
+        Err(e) => {
+            warn!("could not watch {:?}, polling instead: {e}", paths);
+            None
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|x| x.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[tokio::test]
+    async fn watchers_notice_changes_and_missing_directories_fall_back() {
+        let dir = TestDir::new("watch");
+        let (tx, mut changes) = mpsc::unbounded_channel();
+        let _watcher = watch_directories(std::slice::from_ref(&dir.path), tx.clone()).expect("could not start the watcher");
+        std::fs::write(dir.path.join("mod_info.json"), "{}").unwrap();
+        let changed = tokio::time::timeout(Duration::from_secs(5), changes.recv()).await;
+        assert_eq!(changed.ok().flatten(), Some(()));
+        assert!(watch_directories(&[dir.path.join("missing")], tx).is_none());
+    }
+}
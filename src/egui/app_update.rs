@@ -0,0 +1,180 @@
+// Self-updates for rumm itself: checks once at startup, shows the release notes, and installs on request.
+// The actual downloading and swapping is in `updater.rs`
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use eframe::egui::{self, Ui};
+use tokio::runtime::Handle;
+
+use crate::config_and_such::Config;
+use crate::updater::{self, Release, Updater};
+
+use super::notifications::{Notification, Notifications};
+
+#[derive(Clone)]
+enum UpdateState {
+    Checking,
+    UpToDate,
+    /// Every release newer than the running one, newest first
+    Available(Vec<Release>),
+    Installing(Release),
+    /// Where the new binary is, for restarting into it
+    Installed(Release, PathBuf),
+    Failed(String),
+}
+
+/// The update button in the bottom bar, plus the release notes window
+pub struct AppUpdatePanel {
+    state: Arc<Mutex<UpdateState>>,
+    handle: Handle,
+    ctx: egui::Context,
+    notifications: Notifications,
+    notes_open: bool,
+}
+
+impl AppUpdatePanel {
+    /// Starts checking for updates right away
+    pub fn new(handle: Handle, ctx: egui::Context, notifications: Notifications) -> Self {
+        let panel = Self {
+            state: Arc::new(Mutex::new(UpdateState::Checking)),
+            handle,
+            ctx,
+            notifications,
+            notes_open: false,
+        };
+        panel.check();
+        panel
+    }
+
+    fn set_state(&self, state: UpdateState) {
+        *self.state.lock().unwrap() = state;
+        self.ctx.request_repaint();
+    }
+
+    fn check(&self) {
+        self.set_state(UpdateState::Checking);
+        let panel = self.background();
+        self.handle.spawn(async move {
+            let state = match Updater::new(&Config::new()).await {
+                Ok(updater) => match updater.newer_releases() {
+                    newer if newer.is_empty() => UpdateState::UpToDate,
+                    newer => {
+                        panel.notifications.push(Notification::success(
+                            "App update",
+                            format!("rumm {} is available", newer[0].title()),
+                        ));
+                        UpdateState::Available(newer.into_iter().cloned().collect())
+                    }
+                },
+                Err(e) => UpdateState::Failed(format!("Could not check for updates: {e}")),
+            };
+            panel.set_state(state);
+        });
+    }
+
+    fn install(&self, release: Release) {
+        self.set_state(UpdateState::Installing(release.clone()));
+        let panel = self.background();
+        self.handle.spawn(async move {
+            let state = match updater::install(&release).await {
+                Ok(exe) => UpdateState::Installed(release, exe),
+                Err(e) => {
+                    panel
+                        .notifications
+                        .push(Notification::error("App update", e.to_string()));
+                    UpdateState::Failed(e.to_string())
+                }
+            };
+            panel.set_state(state);
+        });
+    }
+
+    /// A copy that can be moved into a background task
+    fn background(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            handle: self.handle.clone(),
+            ctx: self.ctx.clone(),
+            notifications: self.notifications.clone(),
+            notes_open: false,
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let state = self.state.lock().unwrap().clone();
+        match &state {
+            UpdateState::Checking => {
+                ui.weak("Checking for updates...");
+            }
+            UpdateState::UpToDate => {
+                if ui
+                    .small_button(format!("rumm {}", env!("CARGO_PKG_VERSION")))
+                    .on_hover_text("Up to date, click to check again")
+                    .clicked()
+                {
+                    self.check();
+                }
+            }
+            UpdateState::Available(releases) => {
+                if ui.button(format!("⬆ Update to {}", releases[0].title())).clicked() {
+                    self.notes_open = true;
+                }
+            }
+            UpdateState::Installing(release) => {
+                ui.spinner();
+                ui.weak(format!("Installing {}", release.title()));
+            }
+            UpdateState::Installed(release, exe) => {
+                if ui
+                    .button("Restart to finish updating")
+                    .on_hover_text(format!("{} is installed", release.title()))
+                    .clicked()
+                    && let Err(e) = updater::restart(exe)
+                {
+                    self.notifications
+                        .push(Notification::error("App update", format!("Could not restart: {e}")));
+                }
+            }
+            UpdateState::Failed(e) => {
+                if ui.small_button("Check for updates").on_hover_text(e).clicked() {
+                    self.check();
+                }
+            }
+        }
+        if let UpdateState::Available(releases) = state {
+            self.show_release_notes(ui, releases);
+        }
+    }
+
+    fn show_release_notes(&mut self, ui: &mut Ui, releases: Vec<Release>) {
+        let mut install = false;
+        let mut open = self.notes_open;
+        egui::Window::new("Update rumm")
+            .open(&mut open)
+            .default_width(500.0)
+            .show(ui.ctx(), |ui| {
+                ui.label(format!("You're on {}.", env!("CARGO_PKG_VERSION")));
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for release in &releases {
+                        ui.heading(release.title());
+                        if release.prerelease {
+                            ui.weak("Prerelease");
+                        }
+                        ui.label(release.body.as_deref().unwrap_or("No release notes."));
+                        ui.separator();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button(format!("Install {}", releases[0].title())).clicked() {
+                        install = true;
+                    }
+                    ui.weak("The download is checked against its published checksum first.");
+                });
+            });
+        self.notes_open = open && !install;
+        if install {
+            self.install(releases[0].clone());
+        }
+    }
+}
@@ -0,0 +1,335 @@
+// Commands the tabs hand to the background worker, and what comes back when they finish.
+// Every command gets an ID when it's queued (see `tasks.rs`), and its `CommandResponse` carries the same ID
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use color_eyre::eyre::{Report, Result};
+use uuid::Uuid;
+
+use crate::bundle::{self, BundleSummary};
+use crate::cache_usage::{GarbageCollectionPlan, format_bytes};
+use crate::config_and_such::Config;
+use crate::drift::DriftReport;
+use crate::history::UpdateTrigger;
+use crate::lockfile::Lockfile;
+use crate::mod_cache::{Cancelled, ModCache, SyncSummary};
+use crate::thunderstore::{Mod, Version};
+use crate::trash::TrashedItem;
+use crate::verify::VerifySummary;
+
+#[derive(Clone)]
+pub enum AppCommand {
+    UpdateMod(Box<Mod>),
+    UpdateAllMods,
+    CacheModByID(Uuid, Option<String>),
+    SyncModsToRumble,
+    /// Writes an offline bundle to the path. The bool is whether to include every cached version
+    ExportBundle(PathBuf, bool),
+    ImportBundle(PathBuf),
+    WriteLockfile,
+    InstallLocked,
+    /// Puts a mod back on the version it had before its latest change
+    RevertMod(Uuid),
+    /// Moves a version to the trash
+    RemoveVersion(Box<Mod>, Box<Version>),
+    /// Moves a mod to the trash
+    DeleteMod(Box<Mod>),
+    RestoreFromTrash(Vec<TrashedItem>),
+    // bulk actions from the Mods tab, each ends with one sync to Rumble
+    UpdateMods(Vec<Mod>),
+    DeleteMods(Vec<Mod>),
+    /// Trashes every version but the selected and newest one
+    RemoveOldVersions(Vec<Mod>),
+    /// Removes the versions in a plan the user already confirmed
+    CollectGarbage(Box<GarbageCollectionPlan>),
+    /// Compares the Rumble folder with the enabled mods right away, instead of waiting for the watcher
+    CheckDrift,
+    /// Hashes everything the enabled mods deployed and repairs what's damaged
+    VerifyInstallation,
+    /// Caches dependencies that an enabled mod needs but aren't downloaded yet, then syncs
+    AddDependencies(Vec<Mod>),
+}
+
+impl AppCommand {
+    /// Name of the operation, shown in notifications and the task list
+    pub fn source(&self) -> &'static str {
+        match self {
+            AppCommand::UpdateMod(_) => "Update mod",
+            AppCommand::UpdateAllMods => "Update all",
+            AppCommand::CacheModByID(..) => "Add mod",
+            AppCommand::SyncModsToRumble => "Sync to Rumble",
+            AppCommand::ExportBundle(..) => "Export bundle",
+            AppCommand::ImportBundle(_) => "Import bundle",
+            AppCommand::WriteLockfile => "Write lockfile",
+            AppCommand::InstallLocked => "Install from lockfile",
+            AppCommand::RevertMod(_) => "Revert mod",
+            AppCommand::RemoveVersion(..) => "Remove version",
+            AppCommand::DeleteMod(_) => "Delete mod",
+            AppCommand::RestoreFromTrash(_) => "Restore from trash",
+            AppCommand::UpdateMods(_) => "Update mods",
+            AppCommand::DeleteMods(_) => "Delete mods",
+            AppCommand::RemoveOldVersions(_) => "Remove old versions",
+            AppCommand::CollectGarbage(_) => "Clean up cache",
+            AppCommand::CheckDrift => "Check Rumble folder",
+            AppCommand::VerifyInstallation => "Verify installation",
+            AppCommand::AddDependencies(_) => "Add dependencies",
+        }
+    }
+
+    /// The mod the command is about, if it's about just one
+    pub fn mod_id(&self) -> Option<Uuid> {
+        match self {
+            AppCommand::UpdateMod(m)
+            | AppCommand::RemoveVersion(m, _)
+            | AppCommand::DeleteMod(m) => Some(m.uuid),
+            AppCommand::CacheModByID(id, _) | AppCommand::RevertMod(id) => Some(*id),
+            AppCommand::RestoreFromTrash(items) => match items.as_slice() {
+                [item] => Some(item.mod_to_change.uuid),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// True if the command changes this mod, even as part of a bulk action
+    pub fn affects_mod(&self, mod_id: &Uuid) -> bool {
+        match self {
+            AppCommand::UpdateMods(mods) | AppCommand::DeleteMods(mods) | AppCommand::RemoveOldVersions(mods) => {
+                mods.iter().any(|x| x.uuid == *mod_id)
+            }
+            AppCommand::RestoreFromTrash(items) => items.iter().any(|x| x.mod_to_change.uuid == *mod_id),
+            _ => self.mod_id() == Some(*mod_id),
+        }
+    }
+}
+
+/// What a command produced when it succeeded
+#[derive(Debug, Clone)]
+pub enum CommandOutcome {
+    /// The mod that was added to the cache
+    Cached(Mod),
+    Updated(Mod),
+    UpdatedAll,
+    Synced(SyncSummary),
+    Exported(BundleSummary, PathBuf),
+    Imported(BundleSummary),
+    Locked { mods: usize, path: PathBuf },
+    InstalledLocked(SyncSummary),
+    /// The version it went back to
+    Reverted(String),
+    /// Bulk actions also sync once they're done, and keep going past mods they couldn't trash
+    Trashed {
+        items: Vec<TrashedItem>,
+        summary: Option<SyncSummary>,
+        /// Mods that couldn't be trashed, with why
+        failed: Vec<String>,
+    },
+    Restored(Vec<TrashedItem>),
+    UpdatedMods(usize, SyncSummary),
+    /// Bytes freed
+    CollectedGarbage(u64),
+    DriftChecked(DriftReport),
+    Verified(VerifySummary),
+    AddedDependencies(Vec<Mod>, SyncSummary),
+}
+
+impl CommandOutcome {
+    /// True if the command could have added, removed or changed anything in the mod cache
+    pub fn changes_cache(&self) -> bool {
+        !matches!(
+            self,
+            CommandOutcome::Synced(_)
+                | CommandOutcome::Exported(..)
+                | CommandOutcome::Locked { .. }
+                | CommandOutcome::DriftChecked(_)
+        )
+    }
+
+    /// True if part of a bulk action failed, the rest still went through
+    pub fn is_partial(&self) -> bool {
+        matches!(self, CommandOutcome::Trashed { failed, .. } if !failed.is_empty())
+    }
+}
+
+impl std::fmt::Display for CommandOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandOutcome::Cached(m) => write!(f, "Added {}", m.name),
+            CommandOutcome::Updated(m) => write!(f, "{} is up to date", m.name),
+            CommandOutcome::UpdatedAll => write!(f, "All mods are up to date"),
+            CommandOutcome::Synced(summary) => write!(f, "Synced {summary}"),
+            CommandOutcome::Exported(summary, path) => write!(f, "Exported {summary} to {:?}", path),
+            CommandOutcome::Imported(summary) => write!(f, "Imported {summary}"),
+            CommandOutcome::Locked { mods, path } => write!(f, "Locked {mods} mods in {:?}", path),
+            CommandOutcome::InstalledLocked(summary) => {
+                write!(f, "Installed the mods from the lockfile, synced {summary}")
+            }
+            CommandOutcome::Reverted(version) => write!(f, "Reverted to {version}"),
+            CommandOutcome::Trashed { items, summary, failed } => {
+                match items.as_slice() {
+                    [item] => write!(f, "Moved {item} to the trash")?,
+                    _ => write!(f, "Moved {} items to the trash", items.len())?,
+                }
+                if let Some(summary) = summary {
+                    write!(f, ", synced {summary}")?;
+                }
+                match failed.as_slice() {
+                    [] => Ok(()),
+                    _ => write!(f, ". Couldn't trash {}", failed.join(", ")),
+                }
+            }
+            CommandOutcome::Restored(items) => match items.as_slice() {
+                [item] => write!(f, "Put {item} back"),
+                _ => write!(f, "Put {} items back", items.len()),
+            },
+            CommandOutcome::UpdatedMods(count, summary) => write!(f, "Updated {count} mods, synced {summary}"),
+            CommandOutcome::CollectedGarbage(freed) => write!(f, "Freed {}", format_bytes(*freed)),
+            CommandOutcome::DriftChecked(report) if report.is_empty() => {
+                write!(f, "The Rumble folder matches your enabled mods")
+            }
+            CommandOutcome::DriftChecked(report) => write!(f, "The Rumble folder drifted: {report}"),
+            CommandOutcome::Verified(summary) => write!(f, "Verified {summary}"),
+            CommandOutcome::AddedDependencies(mods, summary) => {
+                let names: Vec<&str> = mods.iter().map(|x| x.name.as_str()).collect();
+                write!(f, "Added {}, synced {summary}", names.join(", "))
+            }
+        }
+    }
+}
+
+/// Why a command didn't succeed
+#[derive(Debug, Clone)]
+pub enum CommandError {
+    Cancelled,
+    /// Shared so responses can be cloned without losing the error chain
+    Failed(Arc<Report>),
+}
+
+impl From<Report> for CommandError {
+    fn from(report: Report) -> Self {
+        if report.is::<Cancelled>() {
+            CommandError::Cancelled
+        } else {
+            CommandError::Failed(Arc::new(report))
+        }
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::Cancelled => write!(f, "Cancelled"),
+            // the alternate form includes every cause, not just the outermost message
+            CommandError::Failed(report) => write!(f, "{report:#}"),
+        }
+    }
+}
+
+/// Sent back to the UI when a command finishes
+pub struct CommandResponse {
+    /// The ID the command got when it was queued
+    pub id: u64,
+    pub command: AppCommand,
+    pub result: Result<CommandOutcome, CommandError>,
+}
+
+/// Runs one command on the worker
+pub async fn run_command(cache: &mut ModCache, config: &Config, cmd: AppCommand) -> Result<CommandOutcome> {
+    Ok(match cmd {
+        AppCommand::UpdateMod(mod_to_update) => {
+            cache.update_mod(config, &mod_to_update, UpdateTrigger::Manual).await?;
+            CommandOutcome::Updated(*mod_to_update)
+        }
+        AppCommand::CacheModByID(id, version) => {
+            let cached = cache.cache_mod_by_mod_id(&id.to_string(), version.as_ref()).await?;
+            // picked by the user, so it's not just a dependency anymore
+            cache.options().update(|options| options.set_auto_installed(&id, false))?;
+            CommandOutcome::Cached(cached)
+        }
+        AppCommand::UpdateAllMods => {
+            cache.update_all_mods(config, UpdateTrigger::UpdateAll).await?;
+            CommandOutcome::UpdatedAll
+        }
+        AppCommand::SyncModsToRumble => CommandOutcome::Synced(cache.sync_all_mods_to_rumble(config).await?),
+        AppCommand::ExportBundle(path, all_versions) => {
+            let summary = bundle::export_bundle(config, cache, all_versions, &path)?;
+            CommandOutcome::Exported(summary, path)
+        }
+        AppCommand::ImportBundle(path) => CommandOutcome::Imported(bundle::import_bundle(config, cache, &path)?),
+        AppCommand::WriteLockfile => {
+            let path = Lockfile::default_path(config);
+            let lockfile = cache.write_lockfile(config, &path).await?;
+            CommandOutcome::Locked {
+                mods: lockfile.mods.len(),
+                path,
+            }
+        }
+        AppCommand::InstallLocked => {
+            let path = Lockfile::default_path(config);
+            CommandOutcome::InstalledLocked(cache.install_locked(config, &path).await?)
+        }
+        AppCommand::RevertMod(mod_id) => CommandOutcome::Reverted(cache.revert_mod(config, &mod_id).await?),
+        AppCommand::RemoveVersion(mod_to_change, version) => {
+            CommandOutcome::Trashed {
+                items: vec![cache.trash_version(config, &mod_to_change, &version)?],
+                summary: None,
+                failed: vec![],
+            }
+        }
+        AppCommand::DeleteMod(mod_to_remove) => {
+            CommandOutcome::Trashed {
+                items: vec![cache.trash_mod(config, &mod_to_remove)?],
+                summary: None,
+                failed: vec![],
+            }
+        }
+        AppCommand::RestoreFromTrash(items) => {
+            cache.restore_from_trash(config, &items)?;
+            CommandOutcome::Restored(items)
+        }
+        AppCommand::UpdateMods(mods) => {
+            cache.update_mods(config, &mods, UpdateTrigger::Manual).await?;
+            CommandOutcome::UpdatedMods(mods.len(), cache.sync_all_mods_to_rumble(config).await?)
+        }
+        AppCommand::DeleteMods(mods) => {
+            // one bad mod shouldn't leave the rest half done, so collect failures and sync once
+            let mut trashed = vec![];
+            let mut failed = vec![];
+            for mod_to_remove in &mods {
+                match cache.trash_mod(config, mod_to_remove) {
+                    Ok(item) => trashed.push(item),
+                    Err(e) => failed.push(format!("{} ({e})", mod_to_remove.name)),
+                }
+            }
+            CommandOutcome::Trashed {
+                items: trashed,
+                summary: Some(cache.sync_all_mods_to_rumble(config).await?),
+                failed,
+            }
+        }
+        AppCommand::RemoveOldVersions(mods) => {
+            let mut trashed = vec![];
+            let mut failed = vec![];
+            for mod_to_update in &mods {
+                if let Err(e) = cache.trash_old_versions(config, mod_to_update, &mut trashed) {
+                    failed.push(format!("{} ({e})", mod_to_update.name));
+                }
+            }
+            CommandOutcome::Trashed {
+                items: trashed,
+                summary: Some(cache.sync_all_mods_to_rumble(config).await?),
+                failed,
+            }
+        }
+        AppCommand::CollectGarbage(plan) => CommandOutcome::CollectedGarbage(cache.collect_garbage(config, &plan)?),
+        AppCommand::CheckDrift => CommandOutcome::DriftChecked(cache.check_drift(config)?),
+        AppCommand::VerifyInstallation => CommandOutcome::Verified(cache.verify_installation(config).await?),
+        AppCommand::AddDependencies(mods) => {
+            for dependency in &mods {
+                cache.cache_dependency(&dependency.uuid).await?;
+            }
+            CommandOutcome::AddedDependencies(mods, cache.sync_all_mods_to_rumble(config).await?)
+        }
+    })
+}
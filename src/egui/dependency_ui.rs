@@ -0,0 +1,146 @@
+// Window showing what a mod needs (the whole tree, with resolved versions) and what needs it.
+// Opened from both the Mods tab and the browser. Everything is worked out once when it opens,
+// and again whenever a new snapshot is published or the mod options change
+
+use std::sync::mpsc;
+
+use eframe::egui::{self, CollapsingHeader, Id, RichText, Ui};
+
+use crate::dependencies::{DependencyNode, Dependent};
+use crate::thunderstore::Mod;
+
+use super::app_state::{AppSnapshot, AppState};
+
+pub struct DependencyWindow {
+    library: Mod,
+    /// Snapshot revision everything below was worked out from
+    revision: u64,
+    tree: DependencyNode,
+    installed_dependents: Vec<Dependent>,
+    /// Names of Thunderstore packages that depend on it
+    thunderstore_dependents: Vec<String>,
+    /// Enabling a mod or picking another version changes the tree without a new snapshot
+    options_changed: mpsc::Receiver<()>,
+}
+
+impl DependencyWindow {
+    pub fn new(state: &AppState, library: Mod) -> Self {
+        let snapshot = state.snapshot();
+        let (tree, installed_dependents, thunderstore_dependents) = Self::resolve(state, &snapshot, &library);
+        Self {
+            library,
+            revision: snapshot.revision,
+            tree,
+            installed_dependents,
+            thunderstore_dependents,
+            options_changed: state.options().subscribe(),
+        }
+    }
+
+    fn resolve(state: &AppState, snapshot: &AppSnapshot, library: &Mod) -> (DependencyNode, Vec<Dependent>, Vec<String>) {
+        let options = state.options().snapshot();
+        let mut thunderstore_dependents: Vec<String> = snapshot
+            .cache
+            .thunderstore_dependents(library)
+            .iter()
+            .map(|x| x.full_name.clone())
+            .collect();
+        thunderstore_dependents.sort();
+        (
+            snapshot.cache.dependency_tree(library, &options),
+            snapshot.cache.installed_dependents(&library.uuid, &options),
+            thunderstore_dependents,
+        )
+    }
+
+    /// Returns false once the window is closed
+    pub fn show(&mut self, ui: &mut Ui, state: &AppState) -> bool {
+        let snapshot = state.snapshot();
+        if self.options_changed.try_iter().count() > 0 || snapshot.revision != self.revision {
+            (self.tree, self.installed_dependents, self.thunderstore_dependents) =
+                Self::resolve(state, &snapshot, &self.library);
+            self.revision = snapshot.revision;
+        }
+        let mut open = true;
+        egui::Window::new(format!("{} Dependencies", self.library.name))
+            .collapsible(false)
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    ui.strong("Needs");
+                    if self.tree.children.is_empty() {
+                        ui.weak("Nothing, it doesn't depend on other mods.");
+                    }
+                    for child in &self.tree.children {
+                        show_node(ui, child, Id::new(self.library.uuid));
+                    }
+                    ui.separator();
+                    self.show_dependents(ui);
+                });
+            });
+        open
+    }
+
+    fn show_dependents(&self, ui: &mut Ui) {
+        ui.strong("Needed by");
+        let enabled = self.installed_dependents.iter().filter(|x| x.enabled).count();
+        match enabled {
+            0 => ui.label("No enabled mod needs it, so it's safe to disable or remove."),
+            1 => ui.label("1 enabled mod needs it."),
+            n => ui.label(format!("{n} enabled mods need it.")),
+        };
+        for dependent in &self.installed_dependents {
+            let mut text = RichText::new(&dependent.dependent.name);
+            if !dependent.enabled {
+                text = text.weak();
+            }
+            ui.horizontal(|ui| {
+                ui.label(text);
+                if !dependent.direct {
+                    ui.weak("(through another dependency)");
+                }
+                if !dependent.enabled {
+                    ui.weak("(disabled)");
+                }
+            });
+        }
+        CollapsingHeader::new(format!(
+            "{} packages on Thunderstore depend on it",
+            self.thunderstore_dependents.len()
+        ))
+        .id_salt("Thunderstore Dependents")
+        .show(ui, |ui| {
+            for name in &self.thunderstore_dependents {
+                ui.label(name);
+            }
+        });
+    }
+}
+
+/// `parent` comes from the path down from the root, a mod can show up in more than one branch
+fn show_node(ui: &mut Ui, node: &DependencyNode, parent: Id) {
+    let version = node.version.as_deref().unwrap_or("no allowed version");
+    let status = match (node.cached, node.enabled) {
+        (false, _) => "not downloaded",
+        (true, true) => "enabled",
+        (true, false) => "disabled",
+    };
+    let label = format!("{} {version} ({status})", node.name);
+    if node.cycle {
+        ui.label(format!("{label} ⟲ dependency cycle"));
+        return;
+    }
+    if node.children.is_empty() {
+        ui.label(label);
+        return;
+    }
+    let id = parent.with(node.mod_id);
+    CollapsingHeader::new(label)
+        .id_salt(id)
+        .default_open(true)
+        .show(ui, |ui| {
+            for child in &node.children {
+                show_node(ui, child, id);
+            }
+        });
+}
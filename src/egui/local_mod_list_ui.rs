@@ -1,26 +1,222 @@
 // src/gui/LocalModList.rs
 
-use crate::thunderstore::{Mod, ModList, Version};
-use crate::config_and_such::LocalModOptions;
-use crate::{mod_cache::ModCache, config_and_such::Config};
+use crate::cache_usage::{GarbageCollectionPlan, format_bytes};
+use crate::history::{ModHistory, UpdateTrigger, format_time_ago};
+use crate::mod_cache::{AvailableUpdate, PackageStatus};
+use crate::thunderstore::{Mod, Version};
+use crate::config_and_such::{Config, LocalModOptions, UpdatePolicy};
+use crate::rules::IncompatibilityRule;
+use crate::trash::TrashedItem;
+use crate::version_requirement::VersionRequirement;
 use color_eyre::eyre::{Result, eyre};
-use eframe::egui::{self, Button, Checkbox, Image, Label, Ui};
+use eframe::egui::{
+    self, Button, Key, KeyboardShortcut, Modifiers, RichText, SelectableLabel, TextEdit, Ui,
+};
 use uuid::Uuid;
 
-use std::sync::mpsc::{self, Receiver, Sender};
-use std::thread;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc;
 
-use super::{AppCommand, TabResult};
+use super::app_state::{AppSnapshot, AppState};
+use super::commands::{CommandOutcome, CommandResponse};
+use super::dependency_ui::DependencyWindow;
+use super::notifications::{Notification, Notifications};
+use super::{AppCommand, TabResult, mod_icon};
 
 /// All data for the “Mods” tab lives here.
 pub struct LocalModsTab {
-    cache: ModCache,
-    options: LocalModOptions,
-    /// Receiver for results coming back from the worker thread.
-    result_rx: Option<Receiver<Result<()>>>,
+    state: AppState,
+    notifications: Notifications,
+    /// Commands this tab started that haven't finished yet, by task ID
+    in_flight: HashMap<u64, AppCommand>,
     // trying to emulate Elm with this one, might wanna switch to iced instead of egui at some point
     pending_changes: Vec<PendingChange>,
+    /// Set while the "Clean Up Cache" preview is open
+    garbage_collection_preview: Option<GarbageCollectionPlan>,
+    /// Open from a mod's Dependencies button
+    dependency_window: Option<DependencyWindow>,
+    /// Version requirements being typed in, by mod. Applied once the text box loses focus
+    requirement_edits: HashMap<Uuid, String>,
+    /// The mod whose version history window is open
+    history_window: Option<(Mod, ModHistory)>,
+    /// Changes that can be undone, newest last
+    undo_stack: Vec<UndoableChange>,
+    redo_stack: Vec<UndoableChange>,
+    /// What the next submitted command does to the undo history, if it came from undo or redo
+    next_history_step: Option<HistoryStep>,
+    /// Commands that move the undo history once they finish, by task ID
+    history_steps: HashMap<u64, HistoryStep>,
+    /// Rows picked for bulk actions
+    selected: HashSet<Uuid>,
+    /// Where a shift+click range starts
+    selection_anchor: Option<Uuid>,
+    /// Only mods with this in their name are shown
+    filter: String,
+    /// Bulk action waiting for the user to confirm it
+    bulk_confirmation: Option<(BulkAction, Vec<Mod>)>,
+    /// Enabling that breaks a rule in the rule file, waiting for the user to confirm it
+    conflict_confirmation: Option<(PendingChange, Vec<IncompatibilityRule>)>,
+    /// Set while the list of differences in the Rumble folder is open
+    drift_window: bool,
+    /// Disabling or deleting mods that other enabled mods need, waiting for the user to pick what to do
+    dependents_confirmation: Option<(PendingChange, Vec<Mod>)>,
+    /// Auto-installed mods nothing needs anymore, as of this snapshot revision
+    orphans: Option<(u64, Vec<Mod>)>,
+    /// Newer versions of notify-only mods, worked out alongside `orphans`
+    available_updates: Option<Vec<AvailableUpdate>>,
+    /// Set while the "Updates Available" list is open
+    updates_window: bool,
+    /// Gets a message whenever the mod options change, so `orphans` can be worked out again
+    options_changed: mpsc::Receiver<()>,
 }
+
+/// Oldest changes get forgotten past this
+const MAX_UNDO_STEPS: usize = 50;
+const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+const REDO_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+const REDO_SHORTCUT_ALT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Y);
+
+/// A change the user made that can be undone. Deleted files wait in the trash (see `trash.rs`)
+#[derive(Clone)]
+enum UndoableChange {
+    Enable {
+        mod_to_change: Mod,
+        on: bool,
+    },
+    VersionRequirement {
+        mod_to_change: Mod,
+        from: VersionRequirement,
+        to: VersionRequirement,
+    },
+    UpdatePolicy {
+        mod_to_change: Mod,
+        from: Option<UpdatePolicy>,
+        to: Option<UpdatePolicy>,
+    },
+    SetVersion {
+        mod_to_change: Mod,
+        from: String,
+        to: String,
+    },
+    /// Anything the worker moved to the trash. Redoing runs the same command again
+    Trashed {
+        items: Vec<TrashedItem>,
+        command: Box<AppCommand>,
+    },
+    /// Enables or pins from a bulk action, undone all at once
+    Group {
+        label: String,
+        changes: Vec<UndoableChange>,
+    },
+}
+
+impl UndoableChange {
+    /// The changes that come back from the worker
+    fn from_response(command: &AppCommand, outcome: &CommandOutcome) -> Option<Self> {
+        match outcome {
+            CommandOutcome::Trashed { items, .. } if !items.is_empty() => Some(UndoableChange::Trashed {
+                items: items.clone(),
+                command: Box::new(command.clone()),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Applies (or with `undo`, reverses) the changes that only touch the mod options
+    fn apply_to_options(&self, options: &mut LocalModOptions, undo: bool) {
+        match self {
+            UndoableChange::Enable { mod_to_change, on } => options.set_mod_enabled(mod_to_change, *on != undo),
+            UndoableChange::VersionRequirement { mod_to_change, from, to } => {
+                options.set_version_requirement(&mod_to_change.uuid, if undo { from } else { to }.clone())
+            }
+            UndoableChange::UpdatePolicy { mod_to_change, from, to } => {
+                options.set_update_policy(&mod_to_change.uuid, if undo { *from } else { *to })
+            }
+            UndoableChange::Group { changes, .. } => {
+                for change in changes {
+                    change.apply_to_options(options, undo);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl std::fmt::Display for UndoableChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UndoableChange::Enable { mod_to_change, on } => {
+                write!(f, "{} {}", if *on { "Enable" } else { "Disable" }, mod_to_change.name)
+            }
+            UndoableChange::VersionRequirement { mod_to_change, from, to } => {
+                write!(f, "Change {} from {from} to {to}", mod_to_change.name)
+            }
+            UndoableChange::UpdatePolicy { mod_to_change, from, to } => {
+                let name = |x: &Option<UpdatePolicy>| x.map_or("Default".to_string(), |x| x.to_string());
+                write!(f, "Change {} updates from {} to {}", mod_to_change.name, name(from), name(to))
+            }
+            UndoableChange::SetVersion { mod_to_change, from, to } => {
+                write!(f, "Change {} from {from} to {to}", mod_to_change.name)
+            }
+            UndoableChange::Trashed { items, command } => match items.as_slice() {
+                [item] => write!(f, "{} {item}", command.source()),
+                _ => write!(f, "{} ({} items)", command.source(), items.len()),
+            },
+            UndoableChange::Group { label, .. } => write!(f, "{label}"),
+        }
+    }
+}
+
+/// Things that can be done to every selected mod at once
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BulkAction {
+    Enable,
+    Disable,
+    Pin,
+    Unpin,
+    Update,
+    RemoveOldVersions,
+    Delete,
+}
+
+impl BulkAction {
+    const ALL: [BulkAction; 7] = [
+        BulkAction::Enable,
+        BulkAction::Disable,
+        BulkAction::Pin,
+        BulkAction::Unpin,
+        BulkAction::Update,
+        BulkAction::RemoveOldVersions,
+        BulkAction::Delete,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            BulkAction::Enable => "Enable",
+            BulkAction::Disable => "Disable",
+            BulkAction::Pin => "Pin Version",
+            BulkAction::Unpin => "Follow Latest",
+            BulkAction::Update => "Update",
+            BulkAction::RemoveOldVersions => "Remove Old Versions",
+            BulkAction::Delete => "Delete",
+        }
+    }
+
+    /// The ones that download or remove files get confirmed first
+    fn needs_confirmation(&self) -> bool {
+        matches!(self, BulkAction::Update | BulkAction::RemoveOldVersions | BulkAction::Delete)
+    }
+}
+
+/// How a command moves the undo history once it finishes
+enum HistoryStep {
+    /// A fresh change, it goes on the undo stack
+    New,
+    Undo(UndoableChange),
+    Redo(UndoableChange),
+}
+
 // There are essentially two types of changes: async ones and non-async ones
 // The `TabResult` is for async changes and should be returned from `ui`
 // This struct is for changes that don't need to be async and can run this frame
@@ -30,14 +226,23 @@ enum PendingChange {
         mod_to_change: Mod,
         on: bool,
     },
-    VersionLock {
+    VersionRequirement {
+        mod_to_change: Mod,
+        requirement: VersionRequirement,
+    },
+    UpdatePolicy {
         mod_to_change: Mod,
-        lock: bool,
+        policy: Option<UpdatePolicy>,
     },
     SetVersion {
         mod_to_change: Mod,
         version: Version,
     },
+    /// Downloads a version that isn't cached yet, then selects it
+    CacheVersion {
+        mod_to_change: Mod,
+        version: String,
+    },
     RemoveVersion {
         mod_to_change: Mod,
         version: Version,
@@ -45,70 +250,222 @@ enum PendingChange {
     DeleteMod {
         mod_to_change: Mod,
     },
+    CollectGarbage {
+        plan: GarbageCollectionPlan,
+    },
     UpdateMod {
         mod_to_change: Mod,
     },
+    Revert {
+        mod_to_change: Mod,
+    },
+    /// Adds another mod, like the replacement for a deprecated one
+    AddMod {
+        id: Uuid,
+    },
+    // changes every selected mod
+    Bulk {
+        action: BulkAction,
+        mods: Vec<Mod>,
+    },
     // global changes
+    Undo,
+    Redo,
     UpdateAll,
     SyncToRumble,
+    CheckDrift,
+    VerifyInstallation,
+    WriteLockfile,
+    InstallLocked,
 }
 
 impl LocalModsTab {
-    pub fn new(mod_list: &ModList, options: LocalModOptions) -> Self {
+    pub fn new(state: AppState, notifications: Notifications) -> Self {
         Self {
-            cache: ModCache::new(mod_list),
-            options,
-            result_rx: None,
+            notifications,
+            in_flight: HashMap::new(),
             pending_changes: Vec::new(),
+            garbage_collection_preview: None,
+            history_window: None,
+            dependency_window: None,
+            requirement_edits: HashMap::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            next_history_step: None,
+            history_steps: HashMap::new(),
+            selected: HashSet::new(),
+            selection_anchor: None,
+            filter: String::new(),
+            bulk_confirmation: None,
+            conflict_confirmation: None,
+            drift_window: false,
+            dependents_confirmation: None,
+            orphans: None,
+            available_updates: None,
+            updates_window: false,
+            options_changed: state.options().subscribe(),
+            state,
         }
     }
 
     /// Draws one frame of the tab. Remains synchronous, heavy work is off‑loaded
     /// to a dedicated blocking thread so the UI never stalls.
     pub fn ui(&mut self, ui: &mut Ui) -> TabResult {
-        // check whether the background thread has finished
-        if let Some(rx) = &self.result_rx {
-            while let Ok(r) = rx.try_recv() {
-                if let Err(e) = r {
-                    eprintln!("{e:?}");
-                }
-            }
+        let snapshot = self.state.snapshot();
+        let options = self.state.options().snapshot();
+        if self.options_changed.try_iter().count() > 0
+            || self.orphans.as_ref().is_some_and(|(revision, _)| *revision != snapshot.revision)
+        {
+            self.orphans = None;
+            self.available_updates = None;
         }
+        let orphans = self
+            .orphans
+            .get_or_insert_with(|| {
+                let orphans = snapshot.cache.orphaned_dependencies(&options);
+                (snapshot.revision, orphans.into_iter().cloned().collect())
+            })
+            .1
+            .clone();
+        let available_updates = self
+            .available_updates
+            .get_or_insert_with(|| snapshot.cache.available_updates(&Config::new()))
+            .clone();
+        // forget selected mods that were deleted
+        self.selected.retain(|x| snapshot.pruned_mods.contains_key(x));
+        let filter = self.filter.to_lowercase();
+        let visible: Vec<&Mod> = snapshot
+            .cache
+            .cache_mod_list
+            .iter()
+            .filter(|x| x.name.to_lowercase().contains(&filter))
+            .collect();
+        let visible_ids: Vec<Uuid> = visible.iter().map(|x| x.uuid).collect();
 
-        // we need to update the cache regularly
-        self.cache.update_self_from_cache();
-
-        // collect version changes selected by the user
-        let mut pending_updates: Vec<(Mod, String)> = Vec::new();
-        let config = Config::new();
+        // redo first, since ctrl+z would also match ctrl+shift+z.
+        // Leave the shortcuts alone while typing in the filter, it has its own undo
+        let is_typing = ui.ctx().wants_keyboard_input();
+        ui.input_mut(|input| {
+            if is_typing {
+                return;
+            }
+            if input.consume_shortcut(&REDO_SHORTCUT) || input.consume_shortcut(&REDO_SHORTCUT_ALT) {
+                self.pending_changes.push(PendingChange::Redo);
+            } else if input.consume_shortcut(&UNDO_SHORTCUT) {
+                self.pending_changes.push(PendingChange::Undo);
+            }
+        });
 
         egui::ScrollArea::vertical().show(ui, |ui| -> Result<()> {
             // two top buttons
             ui.horizontal(|ui| -> Result<()> {
-                if ui.button("Update All").clicked() {
+                let undo_button = ui
+                    .add_enabled(!self.undo_stack.is_empty(), Button::new("Undo"))
+                    .on_hover_text(match self.undo_stack.last() {
+                        Some(change) => format!("Undo: {change} ({})", ui.ctx().format_shortcut(&UNDO_SHORTCUT)),
+                        None => "Nothing to undo".to_string(),
+                    });
+                if undo_button.clicked() {
+                    self.pending_changes.push(PendingChange::Undo);
+                }
+                let redo_button = ui
+                    .add_enabled(!self.redo_stack.is_empty(), Button::new("Redo"))
+                    .on_hover_text(match self.redo_stack.last() {
+                        Some(change) => format!("Redo: {change} ({})", ui.ctx().format_shortcut(&REDO_SHORTCUT)),
+                        None => "Nothing to redo".to_string(),
+                    });
+                if redo_button.clicked() {
+                    self.pending_changes.push(PendingChange::Redo);
+                }
+                if ui
+                    .button("Update All")
+                    .on_hover_text("Updates every mod set to update automatically")
+                    .clicked()
+                {
                     self.pending_changes.push(PendingChange::UpdateAll);
                 }
+                if !available_updates.is_empty()
+                    && ui
+                        .button(format!("Updates Available ({})", available_updates.len()))
+                        .on_hover_text("Mods set to notify only, which Update All leaves alone")
+                        .clicked()
+                {
+                    self.updates_window = true;
+                }
                 if ui.button("Sync To Rumble").clicked() {
                     self.pending_changes.push(PendingChange::SyncToRumble);
                 }
+                // badge for when the Rumble folder doesn't match what's enabled
+                if let Some(drift) = self.state.drift().filter(|x| !x.is_empty())
+                    && ui
+                        .button(RichText::new(format!("⚠ {}", drift.len())).color(ui.visuals().warn_fg_color))
+                        .on_hover_text(format!("The Rumble folder doesn't match your enabled mods: {drift}"))
+                        .clicked()
+                {
+                    self.drift_window = true;
+                }
+                if ui
+                    .button("Verify Installation")
+                    .on_hover_text(
+                        "Hashes every file your enabled mods put in Rumble and their cached copies, and repairs anything damaged or missing",
+                    )
+                    .clicked()
+                {
+                    self.pending_changes.push(PendingChange::VerifyInstallation);
+                }
+                if ui
+                    .button("Write Lockfile")
+                    .on_hover_text("Saves the exact version of every enabled mod to config/rumm.lock")
+                    .clicked()
+                {
+                    self.pending_changes.push(PendingChange::WriteLockfile);
+                }
+                if ui
+                    .button("Install From Lockfile")
+                    .on_hover_text("Makes your enabled mods exactly match config/rumm.lock, then syncs")
+                    .clicked()
+                {
+                    self.pending_changes.push(PendingChange::InstallLocked);
+                }
+                // something that isn't about a single mod is running
+                if self.in_flight.values().any(|x| x.mod_id().is_none()) {
+                    ui.spinner();
+                }
+                let total_size: u64 = snapshot.disk_usage.values().map(|x| x.total).sum();
+                if ui
+                    .button("Clean Up Cache")
+                    .on_hover_text(format!(
+                        "Cache is using {}. Removes old versions based on the rules in Settings",
+                        format_bytes(total_size)
+                    ))
+                    .clicked()
+                {
+                    self.garbage_collection_preview =
+                        Some(snapshot.cache.plan_garbage_collection(&Config::new())?);
+                }
+                if !orphans.is_empty()
+                    && ui
+                        .button(format!("Remove Unused Dependencies ({})", orphans.len()))
+                        .on_hover_text("Deletes mods that were only installed for another mod that doesn't need them anymore")
+                        .clicked()
+                {
+                    self.bulk_confirmation = Some((BulkAction::Delete, orphans.clone()));
+                }
                 Ok(())
             }).inner?;
+            self.show_selection_bar(ui, &snapshot, &visible_ids);
             let grid_result = egui::Grid::new("Mod Grid").striped(true).show(ui, |ui| {
-                for original_mod_from_thunderstore in &self.cache.cache_mod_list {
-                    let mod_from_cache = match self
-                        .cache
-                        .prune_extra_versions_from_mod(&config, &original_mod_from_thunderstore)
-                    {
-                        Ok(m) => m,
-                        Err(e) => return Err(e),
+                for original_mod_from_thunderstore in &visible {
+                    let Some(mod_from_cache) = snapshot.pruned_mods.get(&original_mod_from_thunderstore.uuid)
+                    else {
+                        continue;
                     };
-                    let is_mod_enabled = match self.options.is_mod_enabled(&mod_from_cache) {
+                    let is_mod_enabled = match options.is_mod_enabled(&mod_from_cache) {
                         Ok(enabled) => enabled,
                         Err(e) => return Err(e),
                     };
-                    let current = self
-                        .options
-                        .get_mod_options_mut(original_mod_from_thunderstore.uuid.to_string())
+                    let current = options
+                        .get_mod_options(original_mod_from_thunderstore.uuid.to_string())
                         .ok_or(eyre!(
                             "Mod options file not found for {}",
                             original_mod_from_thunderstore.name
@@ -120,27 +477,115 @@ impl LocalModsTab {
                     ui.checkbox(&mut mod_enabled_mut, "Enabled");
                     // just a hacky way to convert from the `mut bool` to the `enable/disable mod` functions
                     if mod_enabled_mut != is_mod_enabled {
-                        self.pending_changes
-                            .push(PendingChange::Enable { mod_to_change: mod_from_cache.clone(), on: mod_enabled_mut });
+                        let change = PendingChange::Enable { mod_to_change: mod_from_cache.clone(), on: mod_enabled_mut };
+                        self.push_checking_conflicts(&snapshot, change);
                     }
 
                     if let Some(first) = original_mod_from_thunderstore.versions.first() {
                         // image
-                        ui.add_enabled(is_mod_enabled, Image::new(first.icon.clone()));
-                        // name
-                        ui.add_enabled(is_mod_enabled, Label::new(&first.name));
-                        //ui.label(&first.name);
-                        //version lock checkbox
-                        let mut version_lock = current.version_lock.clone();
-                        let checkbox = ui.add_enabled(
-                            is_mod_enabled,
-                            Checkbox::new(&mut version_lock, "Lock Verison"),
-                        ).on_hover_text("If checked, the mod will not update unless you use the version selector to the right");
-                        if checkbox.changed() {
-                            self.pending_changes.push(
-                                PendingChange::VersionLock { mod_to_change: mod_from_cache.clone(), lock: version_lock });
+                        ui.add_enabled(is_mod_enabled, mod_icon(self.state.icons(), first));
+                        // name, click it to select the row
+                        let mut name = RichText::new(&first.name);
+                        if !is_mod_enabled {
+                            name = name.weak();
+                        }
+                        let package_rule = snapshot.cache.rules().package_rule(mod_from_cache);
+                        let blocked = snapshot.cache.rules().is_blocked(mod_from_cache);
+                        if blocked {
+                            name = name.strikethrough();
+                        }
+                        if current.auto_installed {
+                            name = name.italics();
+                        }
+                        let is_selected = self.selected.contains(&mod_from_cache.uuid);
+                        let mut name_label = ui.add(SelectableLabel::new(is_selected, name));
+                        if blocked && let Some(rule) = package_rule {
+                            name_label = name_label
+                                .on_hover_text(format!("Blocked by the rule file, so it won't be synced: {}", rule.reason));
+                        } else if current.auto_installed {
+                            name_label = name_label.on_hover_text("Installed because another mod needs it");
+                        }
+                        if name_label.clicked() {
+                            let modifiers = ui.input(|input| input.modifiers);
+                            self.click_row(mod_from_cache.uuid, &visible_ids, modifiers);
+                        }
+                        // version requirement, shows what's being typed until it loses focus
+                        let mut requirement_text = self
+                            .requirement_edits
+                            .get(&mod_from_cache.uuid)
+                            .cloned()
+                            .unwrap_or_else(|| current.version_requirement.to_string());
+                        let parsed = requirement_text.parse::<VersionRequirement>();
+                        let mut text_edit = TextEdit::singleline(&mut requirement_text)
+                            .desired_width(60.0)
+                            .hint_text("latest");
+                        if parsed.is_err() {
+                            text_edit = text_edit.text_color(ui.visuals().error_fg_color);
+                        }
+                        let hover = match &parsed {
+                            Ok(_) => "Which versions updates can move to, like =1.4.2 (only that version), \
+                                ~1.4 (patch updates only), ^1 (below 2.0) or latest"
+                                .to_string(),
+                            Err(e) => e.to_string(),
+                        };
+                        let text_box = ui.add_enabled(is_mod_enabled, text_edit).on_hover_text(hover);
+                        if text_box.changed() {
+                            self.requirement_edits.insert(mod_from_cache.uuid, requirement_text);
+                        }
+                        // update policy, "Default" follows the one in Settings
+                        let policy_text = match current.update_policy {
+                            Some(policy) => policy.to_string(),
+                            None => "Default".to_string(),
                         };
+                        let mut policy = current.update_policy;
+                        ui.add_enabled_ui(is_mod_enabled, |ui| {
+                            egui::ComboBox::from_id_salt((mod_from_cache.uuid, "update policy"))
+                                .selected_text(policy_text)
+                                .width(90.0)
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut policy, None, "Default");
+                                    for option in UpdatePolicy::ALL {
+                                        ui.selectable_value(&mut policy, Some(option), option.to_string());
+                                    }
+                                })
+                                .response
+                                .on_hover_text("What Update All and the background updater do with this mod");
+                        });
+                        if policy != current.update_policy {
+                            self.pending_changes.push(PendingChange::UpdatePolicy {
+                                mod_to_change: mod_from_cache.clone(),
+                                policy,
+                            });
+                        }
+                        // invalid text is thrown away
+                        if text_box.lost_focus()
+                            && let Some(text) = self.requirement_edits.remove(&mod_from_cache.uuid)
+                            && let Ok(requirement) = text.parse()
+                            && requirement != current.version_requirement
+                        {
+                            self.pending_changes.push(PendingChange::VersionRequirement {
+                                mod_to_change: mod_from_cache.clone(),
+                                requirement,
+                            });
+                        }
                     }
+                    // deprecated, delisted or pulled
+                    let statuses = snapshot.cache.package_status(mod_from_cache, &current.version);
+                    ui.horizontal(|ui| {
+                        if statuses.is_empty() {
+                            return;
+                        }
+                        let text = statuses.iter().map(|x| x.to_string()).collect::<Vec<_>>().join("\n");
+                        ui.colored_label(ui.visuals().warn_fg_color, "⚠").on_hover_text(text);
+                        for status in &statuses {
+                            if let PackageStatus::Deprecated { replacement: Some((id, name)) } = status
+                                && !snapshot.pruned_mods.contains_key(id)
+                                && ui.small_button(format!("Add {name}")).clicked()
+                            {
+                                self.pending_changes.push(PendingChange::AddMod { id: *id });
+                            }
+                        }
+                    });
                     // version selector
                     let old_version = current.version.clone();
                     let combo_box =
@@ -148,21 +593,39 @@ impl LocalModsTab {
                             .selected_text(&current.version);
                     // wrap combobox in enabled check to let it be disabled when auto-updates are off
                     let mut selected_version = current.version.clone();
+                    // picking a version moves an exact pin along, so any version is fine then
+                    let is_pinned = current.version_requirement.is_pinned_to(&current.version);
                     ui.add_enabled_ui(
-                        self.options
-                            .get_version_lock(&mod_from_cache.uuid)
-                            .unwrap_or(false)
-                            && is_mod_enabled,
+                        !current.version_requirement.is_latest() && is_mod_enabled,
                         |ui| {
                             combo_box.show_ui(ui, |ui| {
                                 for v in &original_mod_from_thunderstore.versions {
                                     // the ui for each element in the combo box
                                     ui.horizontal(|ui| {
-                                        ui.selectable_value(
-                                            &mut selected_version,
-                                            v.version_number.clone(),
-                                            &v.version_number,
+                                        let allowed = snapshot
+                                            .cache
+                                            .rules()
+                                            .check_version(original_mod_from_thunderstore, &v.version_number)
+                                            .and_then(|_| match is_pinned || current.version_requirement.matches(&v.version_number) {
+                                                true => Ok(()),
+                                                false => Err(eyre!("Doesn't match {}", current.version_requirement)),
+                                            });
+                                        let label = ui.add_enabled(
+                                            allowed.is_ok(),
+                                            SelectableLabel::new(
+                                                selected_version == v.version_number,
+                                                match v.is_active {
+                                                    true => v.version_number.clone(),
+                                                    false => format!("{} (pulled)", v.version_number),
+                                                },
+                                            ),
                                         );
+                                        if label.clicked() {
+                                            selected_version = v.version_number.clone();
+                                        }
+                                        if let Err(e) = allowed {
+                                            label.on_disabled_hover_text(e.to_string());
+                                        }
                                         // show delete button if the mod is available locally
                                         if mod_from_cache
                                             .versions
@@ -176,11 +639,17 @@ impl LocalModsTab {
                                                 .on_hover_text("Delete version")
                                                 .clicked()
                                             {
-                                                //self.cache.remove_old_versions_from_cache(&config, &mod_from_cache);
                                                 self.pending_changes.push(
                                                     PendingChange::RemoveVersion { mod_to_change: mod_from_cache.clone(), version: v.clone() },
                                                 );
                                             };
+                                            if let Some(size) = snapshot
+                                                .disk_usage
+                                                .get(&mod_from_cache.uuid)
+                                                .and_then(|x| x.versions.get(&v.version_number))
+                                            {
+                                                ui.weak(format_bytes(*size));
+                                            }
                                         }
                                     });
                                 }
@@ -188,14 +657,46 @@ impl LocalModsTab {
                         },
                     );
                     if old_version != selected_version {
-                        pending_updates.push((mod_from_cache.clone(), selected_version.clone()));
-                        let version = mod_from_cache.versions.iter().find(|x| x.version_number == selected_version).ok_or(eyre!("Mod version {selected_version} not found in mod {}", mod_from_cache.name))?;
-                        self.pending_changes.push(
-                            PendingChange::SetVersion { mod_to_change: mod_from_cache.clone(), version: version.clone() },
-                        );
+                        match mod_from_cache.versions.iter().find(|x| x.version_number == selected_version) {
+                            Some(version) => self.pending_changes.push(
+                                PendingChange::SetVersion { mod_to_change: mod_from_cache.clone(), version: version.clone() },
+                            ),
+                            // not downloaded yet, it gets selected once it's in the cache
+                            None => self.pending_changes.push(
+                                PendingChange::CacheVersion { mod_to_change: mod_from_cache.clone(), version: selected_version.clone() },
+                            ),
+                        }
+                    }
+                    // disk usage of all cached versions
+                    match snapshot.disk_usage.get(&mod_from_cache.uuid) {
+                        Some(usage) => ui.label(format_bytes(usage.total)),
+                        None => ui.label(""),
+                    };
+                    let is_busy = self
+                        .in_flight
+                        .values()
+                        .any(|x| x.affects_mod(&mod_from_cache.uuid));
+                    let is_delisted = statuses.contains(&PackageStatus::Delisted);
+                    if is_busy {
+                        ui.spinner();
+                    } else if ui
+                        .add_enabled(!is_delisted, Button::new("Update"))
+                        .on_disabled_hover_text("No longer on Thunderstore, so there's nothing to update to")
+                        .clicked()
+                    {
+                        self.pending_changes
+                            .push(PendingChange::UpdateMod { mod_to_change: mod_from_cache.clone() });
                     }
-                    if ui.button("Update").clicked() {
-                        self.pending_changes.push(PendingChange::UpdateAll);
+                    if ui
+                        .button("Dependencies")
+                        .on_hover_text("What this mod needs, and which mods need it")
+                        .clicked()
+                    {
+                        self.dependency_window = Some(DependencyWindow::new(&self.state, mod_from_cache.clone()));
+                    }
+                    if ui.button("History").clicked() {
+                        let history = ModHistory::load(&Config::new(), &mod_from_cache.uuid);
+                        self.history_window = Some((mod_from_cache.clone(), history));
                     }
                     // Delete Button
                     if ui
@@ -203,8 +704,8 @@ impl LocalModsTab {
                         .on_hover_text("Delete mod")
                         .clicked()
                     {
-                        self.pending_changes
-                            .push(PendingChange::DeleteMod { mod_to_change: mod_from_cache.clone() });
+                        let change = PendingChange::DeleteMod { mod_to_change: mod_from_cache.clone() };
+                        self.push_checking_conflicts(&snapshot, change);
                     };
                     ui.end_row();
                 }
@@ -216,125 +717,822 @@ impl LocalModsTab {
             }
             Ok(())
         });
+        self.show_garbage_collection_preview(ui);
+        self.show_bulk_confirmation(ui);
+        self.show_conflict_confirmation(ui);
+        self.show_history_window(ui);
+        if let Some(window) = &mut self.dependency_window
+            && !window.show(ui, &self.state)
+        {
+            self.dependency_window = None;
+        }
+        self.show_drift_window(ui);
+        self.show_updates_window(ui, &available_updates);
+        self.show_dependents_confirmation(ui);
+        self.update_state()
+    }
+
+    pub fn command_submitted(&mut self, id: u64, command: AppCommand) {
+        let step = self.next_history_step.take().or_else(|| {
+            matches!(command, AppCommand::RemoveVersion(..) | AppCommand::DeleteMod(_))
+                .then_some(HistoryStep::New)
+        });
+        if let Some(step) = step {
+            self.history_steps.insert(id, step);
+        }
+        self.in_flight.insert(id, command);
+    }
+
+    /// Picks up the results of finished commands. The mods themselves come from the latest snapshot
+    pub fn handle_response(&mut self, response: &CommandResponse) {
+        self.in_flight.remove(&response.id);
+        if let Some(step) = self.history_steps.remove(&response.id) {
+            self.finish_history_step(step, response);
+        }
+        let Ok(outcome) = &response.result else {
+            return;
+        };
+        // updates and reverts add to the history
+        if outcome.changes_cache() {
+            self.reload_history();
+        }
+        // a version picked in the version selector finished downloading (maybe after a retry), so select it now
+        if let (AppCommand::CacheModByID(_, Some(version)), CommandOutcome::Cached(cached)) =
+            (&response.command, outcome)
+        {
+            match self.select_version(cached, version.clone()) {
+                Ok(Some(from)) => self.push_undo(UndoableChange::SetVersion {
+                    mod_to_change: cached.clone(),
+                    from,
+                    to: version.clone(),
+                }),
+                Ok(None) => {}
+                Err(e) => self.notifications.push(
+                    Notification::error("Change version", e.to_string()).with_mod(Some(cached.uuid)),
+                ),
+            }
+        }
+    }
+
+    /// Moves a change between the undo and redo stacks once the command behind it is done
+    fn finish_history_step(&mut self, step: HistoryStep, response: &CommandResponse) {
+        match (step, &response.result) {
+            (HistoryStep::New, Ok(outcome)) => {
+                if let Some(change) = UndoableChange::from_response(&response.command, outcome) {
+                    self.push_undo(change);
+                }
+            }
+            // redoing a delete trashes it again, under a new trash ID
+            (HistoryStep::Redo(_), Ok(outcome)) => {
+                if let Some(change) = UndoableChange::from_response(&response.command, outcome) {
+                    self.undo_stack.push(change);
+                }
+            }
+            (HistoryStep::Undo(change), Ok(_)) => self.redo_stack.push(change),
+            // put it back so it can be tried again
+            (HistoryStep::Undo(change), Err(_)) => self.undo_stack.push(change),
+            (HistoryStep::Redo(change), Err(_)) => self.redo_stack.push(change),
+            (HistoryStep::New, Err(_)) => {}
+        }
+    }
+
+    /// Records a new change. Anything that was undone can't be redone after this
+    fn push_undo(&mut self, change: UndoableChange) {
+        self.redo_stack.clear();
+        self.undo_stack.push(change);
+        if self.undo_stack.len() > MAX_UNDO_STEPS {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    fn undo(&mut self) -> TabResult {
+        let Some(change) = self.undo_stack.pop() else {
+            return Ok(None);
+        };
+        match self.apply_change(&change, true) {
+            // finishes in the background, `finish_history_step` moves it to the redo stack
+            Ok(Some(command)) => {
+                self.next_history_step = Some(HistoryStep::Undo(change));
+                Ok(Some(command))
+            }
+            Ok(None) => {
+                self.redo_stack.push(change);
+                Ok(None)
+            }
+            Err(e) => {
+                self.undo_stack.push(change);
+                Err(e)
+            }
+        }
+    }
+
+    fn redo(&mut self) -> TabResult {
+        let Some(change) = self.redo_stack.pop() else {
+            return Ok(None);
+        };
+        match self.apply_change(&change, false) {
+            Ok(Some(command)) => {
+                self.next_history_step = Some(HistoryStep::Redo(change));
+                Ok(Some(command))
+            }
+            Ok(None) => {
+                self.undo_stack.push(change);
+                Ok(None)
+            }
+            Err(e) => {
+                self.redo_stack.push(change);
+                Err(e)
+            }
+        }
+    }
+
+    /// Does (or with `undo`, reverses) a change. Changes to files return a command for the worker instead
+    fn apply_change(&mut self, change: &UndoableChange, undo: bool) -> TabResult {
+        match change {
+            UndoableChange::Enable { .. }
+            | UndoableChange::VersionRequirement { .. }
+            | UndoableChange::UpdatePolicy { .. }
+            | UndoableChange::Group { .. } => {
+                self.state
+                    .options()
+                    .update(|options| change.apply_to_options(options, undo))?;
+            }
+            UndoableChange::SetVersion { mod_to_change, from, to } => {
+                self.select_version(mod_to_change, if undo { from } else { to }.clone())?;
+            }
+            UndoableChange::Trashed { items, .. } if undo => {
+                return Ok(Some(AppCommand::RestoreFromTrash(items.clone())));
+            }
+            UndoableChange::Trashed { command, .. } => return Ok(Some(*command.clone())),
+        }
+        Ok(None)
+    }
+
+    /// Sets the mod's version and records it in the mod's history. Returns the version it was on before.
+    /// A mod pinned to its old version gets pinned to the new one, so undoing moves the pin back too
+    fn select_version(&mut self, mod_to_change: &Mod, version: String) -> Result<Option<String>> {
+        let old_version = self.state.options().update(|options| -> Result<Option<String>> {
+            let Some(current) = options.get_mod_options(mod_to_change.uuid.to_string()) else {
+                return Ok(None);
+            };
+            let old_version = current.version.clone();
+            if current.version_requirement.is_pinned_to(&old_version) {
+                options.set_version_requirement(&mod_to_change.uuid, VersionRequirement::exact(&version)?);
+            }
+            options.set_mod_version(&mod_to_change.uuid, &version);
+            Ok(Some(old_version))
+        })??;
+        if let Some(old_version) = &old_version {
+            ModHistory::record(
+                &Config::new(),
+                &mod_to_change.uuid,
+                old_version,
+                &version,
+                UpdateTrigger::Manual,
+            )?;
+            self.reload_history();
+        }
+        Ok(old_version)
+    }
+
+    /// The history window only reads the history file when something could have changed it
+    fn reload_history(&mut self) {
+        if let Some((mod_to_show, history)) = &mut self.history_window {
+            *history = ModHistory::load(&Config::new(), &mod_to_show.uuid);
+        }
+    }
+
+    /// Filter box, selection buttons, and the bulk actions for whatever is selected
+    fn show_selection_bar(&mut self, ui: &mut Ui, snapshot: &AppSnapshot, visible_ids: &[Uuid]) {
+        ui.horizontal(|ui| {
+            ui.label("Filter:");
+            ui.text_edit_singleline(&mut self.filter);
+            if ui.button("Select All").clicked() {
+                self.selected = snapshot.pruned_mods.keys().copied().collect();
+            }
+            if ui
+                .button("Select Filtered")
+                .on_hover_text("Selects the mods that match the filter")
+                .clicked()
+            {
+                self.selected = visible_ids.iter().copied().collect();
+            }
+            if self.selected.is_empty() {
+                ui.weak("Click a mod's name to select it, shift or ctrl click to select more");
+                return;
+            }
+            ui.separator();
+            ui.label(format!("{} selected:", self.selected.len()));
+            for action in BulkAction::ALL {
+                if ui.button(action.label()).clicked() {
+                    // same order as the list
+                    let mods: Vec<Mod> = snapshot
+                        .cache
+                        .cache_mod_list
+                        .iter()
+                        .filter(|x| self.selected.contains(&x.uuid))
+                        .filter_map(|x| snapshot.pruned_mods.get(&x.uuid).cloned())
+                        .collect();
+                    if !action.needs_confirmation() {
+                        self.push_checking_conflicts(snapshot, PendingChange::Bulk { action, mods });
+                    } else if let Some(PendingChange::Bulk { action, mods }) =
+                        self.check_dependents(snapshot, PendingChange::Bulk { action, mods })
+                    {
+                        self.bulk_confirmation = Some((action, mods));
+                    }
+                }
+            }
+            if ui.button("Clear Selection").clicked() {
+                self.selected.clear();
+            }
+        });
+    }
+
+    /// Click selects just this row, ctrl+click toggles it, shift+click selects everything from the last clicked row
+    fn click_row(&mut self, mod_id: Uuid, visible_ids: &[Uuid], modifiers: Modifiers) {
+        let position = |id: Uuid| visible_ids.iter().position(|x| *x == id);
+        if modifiers.shift
+            && let Some(anchor) = self.selection_anchor
+            && let (Some(start), Some(end)) = (position(anchor), position(mod_id))
+        {
+            if !modifiers.command {
+                self.selected.clear();
+            }
+            self.selected
+                .extend(&visible_ids[start.min(end)..=start.max(end)]);
+            return;
+        }
+        if modifiers.command {
+            if !self.selected.remove(&mod_id) {
+                self.selected.insert(mod_id);
+            }
+        } else if self.selected.len() == 1 && self.selected.contains(&mod_id) {
+            self.selected.clear();
+        } else {
+            self.selected = HashSet::from([mod_id]);
+        }
+        self.selection_anchor = Some(mod_id);
+    }
+
+    fn show_bulk_confirmation(&mut self, ui: &mut Ui) {
+        let Some((action, mods)) = &self.bulk_confirmation else {
+            return;
+        };
+        let mut close = false;
+        egui::Window::new(format!("{} {} Mods", action.label(), mods.len()))
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ui.ctx(), |ui| {
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for m in mods {
+                        ui.label(&m.name);
+                    }
+                });
+                match action {
+                    BulkAction::Delete | BulkAction::RemoveOldVersions => {
+                        ui.weak("Removed files go to the trash, so this can be undone until rumm is closed.")
+                    }
+                    _ => ui.weak("Pinned mods stay on their version, the others update within their version requirement."),
+                };
+                ui.weak("Everything gets synced to Rumble once it's done.");
+                ui.horizontal(|ui| {
+                    if ui.button(action.label()).clicked() {
+                        self.pending_changes.push(PendingChange::Bulk {
+                            action: *action,
+                            mods: mods.clone(),
+                        });
+                        close = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+        if close {
+            self.bulk_confirmation = None;
+        }
+    }
 
-        // start the worker if we have a job for it
-        if !pending_updates.is_empty() {
-            let (tx, rx): (Sender<Result<()>>, Receiver<Result<()>>) = mpsc::channel();
-            self.result_rx = Some(rx);
+    /// Queues the change, unless enabling these mods breaks a rule, or disabling them leaves other mods without
+    /// their dependencies. Then it waits for the user to confirm
+    fn push_checking_conflicts(&mut self, snapshot: &AppSnapshot, change: PendingChange) {
+        let Some(change) = self.check_dependents(snapshot, change) else {
+            return;
+        };
+        let mut enabling: Vec<&Mod> = match &change {
+            PendingChange::Enable { mod_to_change, on: true } => vec![mod_to_change],
+            PendingChange::Bulk { action: BulkAction::Enable, mods } => mods.iter().collect(),
+            _ => vec![],
+        };
+        // their dependencies get enabled too
+        let options = self.state.options().snapshot();
+        enabling.extend(snapshot.cache.dependency_closure(&enabling, &options));
+        let conflicts = match enabling.is_empty() {
+            true => vec![],
+            false => snapshot.find_conflicts(self.state.options(), &enabling),
+        };
+        if conflicts.is_empty() {
+            self.pending_changes.push(change);
+        } else {
+            self.conflict_confirmation = Some((change, conflicts));
+        }
+    }
 
-            // Move the necessary data into the thread. Clone or Arc‑wrap as needed.
-            let mut cache = self.cache.clone();
-            let mut options = self.options.clone();
+    /// Disabled cached mods these need, plus the ones that still have to be downloaded
+    fn find_dependencies_to_enable(&self, mods: &[&Mod]) -> (Vec<Mod>, Vec<Mod>) {
+        let snapshot = self.state.snapshot();
+        let options = self.state.options().snapshot();
+        let (cached, missing): (Vec<&Mod>, Vec<&Mod>) = snapshot
+            .cache
+            .dependency_closure(mods, &options)
+            .into_iter()
+            .partition(|x| snapshot.pruned_mods.contains_key(&x.uuid));
+        let to_enable = cached
+            .into_iter()
+            .filter(|x| !options.is_mod_enabled(x).unwrap_or(false))
+            .filter_map(|x| snapshot.pruned_mods.get(&x.uuid).cloned())
+            .collect();
+        (to_enable, missing.into_iter().cloned().collect())
+    }
+
+    /// Gives the change back if nothing enabled needs the mods it disables or deletes.
+    /// Otherwise it waits in `dependents_confirmation` for the user to decide
+    fn check_dependents(&mut self, snapshot: &AppSnapshot, change: PendingChange) -> Option<PendingChange> {
+        let targets: Vec<Uuid> = match &change {
+            PendingChange::Enable { mod_to_change, on: false } | PendingChange::DeleteMod { mod_to_change } => {
+                vec![mod_to_change.uuid]
+            }
+            PendingChange::Bulk {
+                action: BulkAction::Disable | BulkAction::Delete,
+                mods,
+            } => mods.iter().map(|x| x.uuid).collect(),
+            _ => return Some(change),
+        };
+        let options = self.state.options().snapshot();
+        let dependents: Vec<Mod> = snapshot
+            .cache
+            .dependents(&targets, &options)
+            .into_iter()
+            .filter_map(|x| snapshot.pruned_mods.get(&x.uuid).cloned())
+            .collect();
+        if dependents.is_empty() {
+            return Some(change);
+        }
+        self.dependents_confirmation = Some((change, dependents));
+        None
+    }
 
-            thread::spawn(move || {
-                for (m, v) in pending_updates {
-                    let res = change_mod_version_blocking(&mut cache, &mut options, m, v);
-                    let _ = tx.send(res);
+    fn show_dependents_confirmation(&mut self, ui: &mut Ui) {
+        let Some((change, dependents)) = &self.dependents_confirmation else {
+            return;
+        };
+        let (verb, names) = match change {
+            PendingChange::Enable { mod_to_change, .. } => ("Disable", mod_to_change.name.clone()),
+            PendingChange::DeleteMod { mod_to_change } => ("Delete", mod_to_change.name.clone()),
+            PendingChange::Bulk { action, mods } => (action.label(), format!("{} mods", mods.len())),
+            _ => ("Change", String::new()),
+        };
+        let mut picked = None;
+        let mut cancelled = false;
+        egui::Window::new(format!("{verb} {names}?"))
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ui.ctx(), |ui| {
+                ui.label("These enabled mods need it, and won't work without it:");
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for m in dependents {
+                        ui.label(format!("⚠ {}", m.name));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button(format!("{verb} Them Too")).clicked() {
+                        picked = Some(true);
+                    }
+                    if ui.button(format!("Only {verb} {names}")).clicked() {
+                        picked = Some(false);
+                    }
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+        if cancelled {
+            self.dependents_confirmation = None;
+        }
+        if let Some(cascade) = picked
+            && let Some((change, dependents)) = self.dependents_confirmation.take()
+        {
+            self.pending_changes.push(match cascade {
+                true => with_dependents(change, dependents),
+                false => change,
+            });
+        }
+    }
+
+    fn show_conflict_confirmation(&mut self, ui: &mut Ui) {
+        let Some((_, conflicts)) = &self.conflict_confirmation else {
+            return;
+        };
+        let mut confirmed = None;
+        egui::Window::new("Enable Incompatible Mods?")
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ui.ctx(), |ui| {
+                for conflict in conflicts {
+                    ui.label(format!("⚠ {conflict}"));
                 }
+                ui.horizontal(|ui| {
+                    if ui.button("Enable Anyway").clicked() {
+                        confirmed = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        confirmed = Some(false);
+                    }
+                });
             });
+        if let Some(confirmed) = confirmed
+            && let Some((change, _)) = self.conflict_confirmation.take()
+            && confirmed
+        {
+            self.pending_changes.push(change);
+        }
+    }
+
+    /// Option changes happen right away as one undo step, everything else goes to the worker as one command
+    fn apply_bulk_action(&mut self, action: BulkAction, mods: Vec<Mod>) -> TabResult {
+        let options = self.state.options().snapshot();
+        // dependencies of enabled mods that aren't downloaded yet
+        let mut missing = vec![];
+        let changes: Vec<UndoableChange> = match action {
+            BulkAction::Enable | BulkAction::Disable => {
+                let on = action == BulkAction::Enable;
+                let mut mods = mods;
+                if on {
+                    let (dependencies, dependencies_missing) =
+                        self.find_dependencies_to_enable(&mods.iter().collect::<Vec<_>>());
+                    mods.extend(dependencies);
+                    missing = dependencies_missing;
+                }
+                mods.into_iter()
+                    .filter(|x| options.is_mod_enabled(x).is_ok_and(|enabled| enabled != on))
+                    .map(|mod_to_change| UndoableChange::Enable { mod_to_change, on })
+                    .collect()
+            }
+            BulkAction::Pin | BulkAction::Unpin => {
+                let mut changes = vec![];
+                for mod_to_change in mods {
+                    let Some(current) = options.get_mod_options(mod_to_change.uuid.to_string()) else {
+                        continue;
+                    };
+                    // one version that can't be pinned stops the whole thing, nothing is left half pinned
+                    let to = match action {
+                        BulkAction::Pin => VersionRequirement::exact(&current.version)?,
+                        _ => VersionRequirement::Latest,
+                    };
+                    if current.version_requirement != to {
+                        changes.push(UndoableChange::VersionRequirement {
+                            from: current.version_requirement.clone(),
+                            to,
+                            mod_to_change,
+                        });
+                    }
+                }
+                changes
+            }
+            BulkAction::Update => return Ok(Some(AppCommand::UpdateMods(mods))),
+            BulkAction::RemoveOldVersions => return Ok(Some(AppCommand::RemoveOldVersions(mods))),
+            BulkAction::Delete => return Ok(Some(AppCommand::DeleteMods(mods))),
+        };
+        if !changes.is_empty() {
+            let group = UndoableChange::Group {
+                label: format!("{} {} mods", action.label(), changes.len()),
+                changes,
+            };
+            self.state
+                .options()
+                .update(|options| group.apply_to_options(options, false))?;
+            self.push_undo(group);
+        } else if missing.is_empty() {
+            return Ok(None);
+        }
+        // syncs once they're downloaded
+        if !missing.is_empty() {
+            return Ok(Some(AppCommand::AddDependencies(missing)));
+        }
+        // locking doesn't change what's in Rumble
+        if matches!(action, BulkAction::Enable | BulkAction::Disable) {
+            return Ok(Some(AppCommand::SyncModsToRumble));
+        }
+        Ok(None)
+    }
+
+    /// Shows what "Clean Up Cache" would remove and lets the user confirm it
+    fn show_garbage_collection_preview(&mut self, ui: &mut Ui) {
+        let Some(plan) = &self.garbage_collection_preview else {
+            return;
+        };
+        let mut close = false;
+        egui::Window::new("Clean Up Cache")
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ui.ctx(), |ui| {
+                if plan.is_empty() {
+                    ui.label("Nothing to clean up! Change the cache rules in Settings to remove more.");
+                    close = ui.button("OK").clicked();
+                    return;
+                }
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    egui::Grid::new("Garbage Collection Grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for entry in &plan.entries {
+                                ui.label(&entry.mod_name);
+                                ui.label(&entry.version.version_number);
+                                ui.label(format_bytes(entry.bytes));
+                                ui.weak(entry.reason.to_string());
+                                ui.end_row();
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    let delete_text = format!(
+                        "Remove {} versions ({})",
+                        plan.entries.len(),
+                        format_bytes(plan.freed_bytes)
+                    );
+                    if ui.button(delete_text).clicked() {
+                        self.pending_changes
+                            .push(PendingChange::CollectGarbage { plan: plan.clone() });
+                        close = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+        if close {
+            self.garbage_collection_preview = None;
+        }
+    }
+
+    /// Lists every version change of a mod, newest first, with a button to undo the latest one
+    fn show_history_window(&mut self, ui: &mut Ui) {
+        let Some((mod_to_show, history)) = &self.history_window else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new(format!("{} History", mod_to_show.name))
+            .collapsible(false)
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                if history.entries.is_empty() {
+                    ui.label("This mod hasn't changed version yet.");
+                    return;
+                }
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    egui::Grid::new("History Grid").striped(true).show(ui, |ui| {
+                        for entry in history.entries.iter().rev() {
+                            ui.label(format!("{} → {}", entry.from, entry.to));
+                            ui.weak(entry.trigger.to_string());
+                            ui.weak(format_time_ago(entry.timestamp));
+                            ui.end_row();
+                        }
+                    });
+                });
+                if let Some(previous) = history.get_previous_version()
+                    && ui
+                        .button(format!("Revert to {previous}"))
+                        .on_hover_text("Downloads the version again if needed, pins it to that version, and syncs to Rumble")
+                        .clicked()
+                {
+                    self.pending_changes
+                        .push(PendingChange::Revert { mod_to_change: mod_to_show.clone() });
+                }
+            });
+        if !open {
+            self.history_window = None;
+        }
+    }
+
+    fn show_updates_window(&mut self, ui: &mut Ui, updates: &[AvailableUpdate]) {
+        if !self.updates_window {
+            return;
+        }
+        let mut open = true;
+        egui::Window::new("Updates Available")
+            .collapsible(false)
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                if updates.is_empty() {
+                    ui.label("Every notify-only mod is up to date.");
+                }
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    egui::Grid::new("Updates Available Grid").striped(true).show(ui, |ui| {
+                        for update in updates {
+                            ui.label(&update.mod_to_update.name);
+                            ui.label(format!("{} → {}", update.current, update.newest));
+                            if ui.button("Update").clicked() {
+                                self.pending_changes.push(PendingChange::UpdateMod {
+                                    mod_to_change: update.mod_to_update.clone(),
+                                });
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+                if updates.len() > 1 && ui.button("Update All Of These").clicked() {
+                    self.pending_changes.push(PendingChange::Bulk {
+                        action: BulkAction::Update,
+                        mods: updates.iter().map(|x| x.mod_to_update.clone()).collect(),
+                    });
+                }
+            });
+        if !open {
+            self.updates_window = false;
+        }
+    }
+
+    fn show_drift_window(&mut self, ui: &mut Ui) {
+        if !self.drift_window {
+            return;
+        }
+        let drift = self.state.drift().unwrap_or_default();
+        let mut open = true;
+        egui::Window::new("Rumble Folder")
+            .collapsible(false)
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                if drift.is_empty() {
+                    ui.label("The Rumble folder matches your enabled mods.");
+                }
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    let sections = [
+                        ("Missing", drift.missing.iter().map(|x| x.to_string()).collect::<Vec<_>>()),
+                        ("Changed since the last sync", drift.modified.iter().map(|x| x.to_string()).collect()),
+                        ("Not from an enabled mod", drift.unknown.iter().map(|x| x.to_string()).collect()),
+                    ];
+                    for (title, files) in sections.iter().filter(|(_, files)| !files.is_empty()) {
+                        ui.strong(*title);
+                        for file in files {
+                            ui.label(file);
+                        }
+                    }
+                });
+                if !drift.unknown.is_empty() {
+                    ui.weak("Syncing doesn't remove unknown files, delete them by hand if they shouldn't be there.");
+                }
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Sync To Rumble")
+                        .on_hover_text("Puts back missing and changed files")
+                        .clicked()
+                    {
+                        self.pending_changes.push(PendingChange::SyncToRumble);
+                    }
+                    if ui.button("Check Again").clicked() {
+                        self.pending_changes.push(PendingChange::CheckDrift);
+                    }
+                    if ui
+                        .button("Verify Installation")
+                        .on_hover_text("Also checks the cached copies, and downloads damaged ones again")
+                        .clicked()
+                    {
+                        self.pending_changes.push(PendingChange::VerifyInstallation);
+                    }
+                });
+            });
+        if !open {
+            self.drift_window = false;
         }
-        self.update_state()
     }
 
     fn update_state(&mut self) -> Result<Option<AppCommand>> {
-        let config = Config::new();
-        let mut mod_options = LocalModOptions::new(&config);
         // I now realize there can only be one change per frame (user can't click two buttons on the same frame) so this is redundant
-        for change in &self.pending_changes {
+        for change in std::mem::take(&mut self.pending_changes) {
             match change {
-                PendingChange::Enable { mod_to_change, on } => {
-                    self.options.set_mod_enabled(&mod_to_change, &config, *on)?;
+                PendingChange::Enable { mod_to_change, on: true } => {
+                    let (dependencies, missing) = self.find_dependencies_to_enable(&[&mod_to_change]);
+                    let change = match dependencies.is_empty() {
+                        true => UndoableChange::Enable { mod_to_change, on: true },
+                        false => UndoableChange::Group {
+                            label: format!("Enable {} and {} dependencies", mod_to_change.name, dependencies.len()),
+                            changes: [vec![mod_to_change], dependencies]
+                                .concat()
+                                .into_iter()
+                                .map(|mod_to_change| UndoableChange::Enable { mod_to_change, on: true })
+                                .collect(),
+                        },
+                    };
+                    self.state
+                        .options()
+                        .update(|options| change.apply_to_options(options, false))?;
+                    self.push_undo(change);
+                    if !missing.is_empty() {
+                        return Ok(Some(AppCommand::AddDependencies(missing)));
+                    }
+                }
+                PendingChange::Enable { mod_to_change, on: false } => {
+                    self.state
+                        .options()
+                        .update(|options| options.disable_mod(&mod_to_change))?;
+                    self.push_undo(UndoableChange::Enable { mod_to_change, on: false });
                 }
-                PendingChange::VersionLock {
+                PendingChange::VersionRequirement {
                     mod_to_change,
-                    lock,
+                    requirement,
                 } => {
-                    mod_options.set_version_lock(&mod_to_change.uuid, *lock, &config)?;
+                    let from = self.state.options().update(|options| {
+                        let from = options.get_version_requirement(&mod_to_change.uuid).cloned().unwrap_or_default();
+                        options.set_version_requirement(&mod_to_change.uuid, requirement.clone());
+                        from
+                    })?;
+                    self.push_undo(UndoableChange::VersionRequirement {
+                        mod_to_change,
+                        from,
+                        to: requirement,
+                    });
+                }
+                PendingChange::UpdatePolicy { mod_to_change, policy } => {
+                    let from = self.state.options().update(|options| {
+                        let from = options
+                            .get_mod_options(mod_to_change.uuid.to_string())
+                            .and_then(|x| x.update_policy);
+                        options.set_update_policy(&mod_to_change.uuid, policy);
+                        from
+                    })?;
+                    self.push_undo(UndoableChange::UpdatePolicy {
+                        mod_to_change,
+                        from,
+                        to: policy,
+                    });
                 }
                 PendingChange::SetVersion {
                     mod_to_change,
                     version,
                 } => {
-                    mod_options.set_mod_version(
-                        &mod_to_change.uuid,
-                        &version.version_number.to_string(),
-                        &config,
-                    )
-                }?,
+                    if let Some(from) = self.select_version(&mod_to_change, version.version_number.clone())? {
+                        self.push_undo(UndoableChange::SetVersion {
+                            mod_to_change,
+                            from,
+                            to: version.version_number,
+                        });
+                    }
+                }
+                PendingChange::CacheVersion {
+                    mod_to_change,
+                    version,
+                } => {
+                    return Ok(Some(AppCommand::CacheModByID(mod_to_change.uuid, Some(version))));
+                }
                 PendingChange::RemoveVersion {
                     mod_to_change,
                     version,
                 } => {
-                    self.cache.remove_version_from_cache(
-                        &config,
-                        mod_to_change,
-                        version.clone(),
-                    )?;
+                    return Ok(Some(AppCommand::RemoveVersion(Box::new(mod_to_change), Box::new(version))));
                 }
                 PendingChange::DeleteMod { mod_to_change } => {
-                    self.cache.remove_mod_from_cache(&config, mod_to_change)?;
-                    //return Ok(Some(AppCommand))
+                    return Ok(Some(AppCommand::DeleteMod(Box::new(mod_to_change))));
+                }
+                PendingChange::CollectGarbage { plan } => {
+                    return Ok(Some(AppCommand::CollectGarbage(Box::new(plan))));
                 }
                 PendingChange::UpdateMod { mod_to_change } => {
-                    //self.cache.update_mod(&config, mod_to_update);
-                    let update = mod_to_change.clone();
-                    self.pending_changes.clear();
-                    return Ok(Some(AppCommand::UpdateMod(update)));
+                    return Ok(Some(AppCommand::UpdateMod(Box::new(mod_to_change))));
                 }
-                PendingChange::UpdateAll => {
-                    self.pending_changes.clear();
-                    return Ok(Some(AppCommand::UpdateAllMods));
+                PendingChange::AddMod { id } => {
+                    return Ok(Some(AppCommand::CacheModByID(id, None)));
                 }
-                PendingChange::SyncToRumble => {
-                    self.pending_changes.clear();
-                    return Ok(Some(AppCommand::SyncModsToRumble));
+                PendingChange::Revert { mod_to_change } => {
+                    return Ok(Some(AppCommand::RevertMod(mod_to_change.uuid)));
                 }
+                PendingChange::Bulk { action, mods } => return self.apply_bulk_action(action, mods),
+                PendingChange::Undo => return self.undo(),
+                PendingChange::Redo => return self.redo(),
+                PendingChange::UpdateAll => return Ok(Some(AppCommand::UpdateAllMods)),
+                PendingChange::SyncToRumble => return Ok(Some(AppCommand::SyncModsToRumble)),
+                PendingChange::CheckDrift => return Ok(Some(AppCommand::CheckDrift)),
+                PendingChange::VerifyInstallation => return Ok(Some(AppCommand::VerifyInstallation)),
+                PendingChange::WriteLockfile => return Ok(Some(AppCommand::WriteLockfile)),
+                PendingChange::InstallLocked => return Ok(Some(AppCommand::InstallLocked)),
             }
         }
-        self.pending_changes.clear();
-        // update state
-        self.options = mod_options;
         Ok(None)
     }
 }
 
-/// runs inside the background thread
-fn change_mod_version_blocking(
-    cache: &mut ModCache,
-    options: &mut LocalModOptions,
-    mod_to_change: Mod,
-    new_version: String,
-) -> Result<()> {
-    // If the cache API is async, we create a short‑lived Tokio runtime and block on it.
-    let rt = tokio::runtime::Runtime::new()
-        .map_err(|e| eyre!("failed to create a Tokio runtime: {e}"))?;
-
-    let config = Config::new();
-    rt.block_on(async {
-        println!(
-            "Checking version: {}, mod versions: {}",
-            &new_version,
-            &mod_to_change.versions.len()
-        );
-        match cache.does_mod_have_version(&mod_to_change, &new_version) {
-            None | Some(false) => {
-                println!("Adding mod to cache!");
-                cache
-                    .cache_mod_by_mod_id(&mod_to_change.uuid.to_string(), Some(&new_version))
-                    .await?;
-            }
-            Some(true) => {
-                println!(
-                    "Mod Already in cache! Versions: {:?}",
-                    mod_to_change
-                        .versions
-                        .iter()
-                        .map(|x| x.version_number.clone())
-                        .collect::<Vec<String>>()
-                );
-                //options.set_mod_version(&mod_to_change.uuid, new_version, &config);
-            }
-        }
-        Ok(())
-    })
+/// Applies the change to the mods that depend on its mods as well, disabling or deleting them all together
+fn with_dependents(change: PendingChange, dependents: Vec<Mod>) -> PendingChange {
+    match change {
+        PendingChange::Enable { mod_to_change, on: false } => PendingChange::Bulk {
+            action: BulkAction::Disable,
+            mods: [vec![mod_to_change], dependents].concat(),
+        },
+        PendingChange::DeleteMod { mod_to_change } => PendingChange::Bulk {
+            action: BulkAction::Delete,
+            mods: [vec![mod_to_change], dependents].concat(),
+        },
+        PendingChange::Bulk { action, mods } => PendingChange::Bulk {
+            action,
+            mods: [mods, dependents].concat(),
+        },
+        other => other,
+    }
 }
@@ -0,0 +1,116 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use eframe::egui::{self, Color32, RichText, Ui};
+use tracing::Level;
+
+use crate::logging::{LOG_DIRECTORY, LogEntry, get_recent_logs};
+
+use super::TabResult;
+
+/// State for the Logs tab
+pub struct LogsTab {
+    /// Least important level that still gets shown
+    min_level: Level,
+    /// Only show logs about this mod
+    mod_filter: Option<String>,
+}
+
+impl LogsTab {
+    pub fn new() -> Self {
+        Self {
+            min_level: Level::INFO,
+            mod_filter: None,
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) -> TabResult {
+        let logs = get_recent_logs();
+        let mut mod_names: Vec<&String> = logs.iter().filter_map(|x| x.mod_name.as_ref()).collect();
+        mod_names.sort();
+        mod_names.dedup();
+
+        let shown: Vec<&LogEntry> = logs
+            .iter()
+            // tracing's levels count up towards the more verbose ones
+            .filter(|x| x.level <= self.min_level)
+            .filter(|x| self.mod_filter.is_none() || x.mod_name == self.mod_filter)
+            .collect();
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Level")
+                .selected_text(self.min_level.as_str())
+                .show_ui(ui, |ui| {
+                    for level in [Level::ERROR, Level::WARN, Level::INFO, Level::DEBUG] {
+                        ui.selectable_value(&mut self.min_level, level, level.as_str());
+                    }
+                });
+            egui::ComboBox::from_label("Mod")
+                .selected_text(self.mod_filter.as_deref().unwrap_or("All mods"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.mod_filter, None, "All mods");
+                    for name in &mod_names {
+                        ui.selectable_value(&mut self.mod_filter, Some(name.to_string()), *name);
+                    }
+                });
+            if ui
+                .button("Copy")
+                .on_hover_text("Copies the shown logs, for pasting into a bug report")
+                .clicked()
+            {
+                let text: Vec<String> = shown.iter().map(|x| format_entry(x)).collect();
+                ui.ctx().copy_text(text.join("\n"));
+            }
+            ui.weak(format!("Full logs are saved in {LOG_DIRECTORY}"));
+        });
+        ui.separator();
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::vertical()
+            .auto_shrink(false)
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, shown.len(), |ui, rows| {
+                for entry in &shown[rows] {
+                    ui.label(
+                        RichText::new(format_entry(entry))
+                            .monospace()
+                            .color(level_color(ui, &entry.level)),
+                    );
+                }
+            });
+        // new logs can come from any thread, so keep checking while the tab is open
+        ui.ctx().request_repaint_after(Duration::from_secs(1));
+        Ok(None)
+    }
+}
+
+/// One line of the log, like `12:34:56 UTC  INFO [SomeMod] caching mod: SomeMod`
+fn format_entry(entry: &LogEntry) -> String {
+    let secs = entry
+        .time
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default()
+        % 86400;
+    let mod_name = entry
+        .mod_name
+        .as_ref()
+        .map(|x| format!("[{x}] "))
+        .unwrap_or_default();
+    format!(
+        "{:02}:{:02}:{:02} UTC {:>5} {mod_name}{}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        entry.level.as_str(),
+        entry.message
+    )
+}
+
+fn level_color(ui: &Ui, level: &Level) -> Color32 {
+    match *level {
+        Level::ERROR => Color32::RED,
+        Level::WARN => Color32::YELLOW,
+        Level::INFO => ui.visuals().text_color(),
+        _ => ui.visuals().weak_text_color(),
+    }
+}
@@ -1,35 +1,44 @@
-use std::collections::VecDeque;
 use std::path::PathBuf;
 use std::str::FromStr;
-use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
 
 use color_eyre::eyre::Result;
 use eframe::egui;
 use eframe::egui::{Ui, WidgetText};
 use egui_dock::{DockArea, DockState, Style, TabViewer};
-use settings_ui::draw_settings_ui;
+use settings_ui::{SettingsTab, draw_settings_ui};
 use tokio::runtime::Handle;
-use tokio::sync::RwLock;
-use tokio::sync::mpsc::{self, UnboundedSender};
-use uuid::Uuid;
+use tokio::sync::mpsc;
+use tracing::warn;
 
+use crate::icon_cache::{IconCache, THUMBNAIL_SIZE};
 use crate::mod_cache::ModCache;
-use crate::thunderstore::{Mod, ModList};
-use crate::config_and_such::{Config, LocalModOptions};
+use crate::thunderstore::Version;
+use crate::thunderstore::ModList;
+use crate::trash::Trash;
+use crate::config_and_such::{Config, ModOptionsStore};
 
+mod app_state;
+mod app_update;
+mod commands;
+mod dependency_ui;
 mod local_mod_list_ui;
+mod log_ui;
+mod notifications;
+mod tasks;
 mod settings_ui;
 mod thunderstore_browser_ui;
 
+use app_state::AppState;
+use app_update::AppUpdatePanel;
+pub use commands::AppCommand;
+use commands::{CommandError, CommandOutcome, CommandResponse, run_command};
 use local_mod_list_ui::LocalModsTab;
-use thunderstore_browser_ui::draw_thunderstore_browser;
-
-pub enum AppCommand {
-    UpdateMod(Mod),
-    UpdateAllMods,
-    CacheModByID(Uuid, Option<String>),
-    SyncModsToRumble,
-}
+use log_ui::LogsTab;
+use notifications::{Notification, NotificationAction, NotificationPanel, Notifications};
+use tasks::{QueuedCommand, TaskPanel, TaskQueue, TaskState};
+use thunderstore_browser_ui::ThunderstoreBrowserTab;
 
 pub fn start_gui() -> eframe::Result {
     let options = eframe::NativeOptions {
@@ -42,83 +51,130 @@ pub fn start_gui() -> eframe::Result {
         Box::new(move |cc| {
             // Install image loaders, etc.
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Ok(Box::new(MyApp::new()))
+            Ok(Box::new(MyApp::new(&cc.egui_ctx)))
         }),
     )
 }
 
 struct MyApp {
     tabs: MyTabs,
-    cache: Arc<RwLock<ModCache>>,
+    state: AppState,
+    last_options_check: Instant,
+    /// The rule file error that was last shown, so it's only shown once
+    rules_error: Option<String>,
     runtime: RuntimeGuard,
     handle: Handle,
-    runtime_commands: UnboundedSender<AppCommand>,
-    runtime_errors: Arc<Mutex<VecDeque<String>>>,
+    notifications: Notifications,
 }
 
 impl MyApp {
-    fn new() -> Self {
+    fn new(ctx: &egui::Context) -> Self {
         let runtime = start_runtime();
         let mods = ModList::new(PathBuf::from_str("config/thunderstore-mods.json").unwrap()).expect("ModList was not able to be created, sorry it shouldn't crash but I was just writing this part quickly");
-        let (runtime_commands, mut cmd_rx) = mpsc::unbounded_channel::<AppCommand>();
-        let cache = Arc::new(RwLock::new(ModCache::new(&mods)));
-        let runtime_errors = Arc::new(Mutex::new(VecDeque::new()));
+        let options = ModOptionsStore::new(&Config::new()).expect("Could not load the mod options file");
+        // undo only lasts one session, so whatever was deleted last time is gone for good now
+        {
+            let config = Config::new();
+            if let Err(e) = Trash::new(&config).empty().and_then(|_| ModCache::remove_unused_blobs(&config)) {
+                warn!("could not empty the trash: {e}");
+            }
+        }
+        let (runtime_commands, mut cmd_rx) = mpsc::unbounded_channel::<QueuedCommand>();
+        let tasks = TaskQueue::new(runtime_commands);
+        let (responses, response_rx) = std::sync::mpsc::channel::<CommandResponse>();
+        let state = AppState::new(&mods, options.clone());
+        state.watch_index(&runtime.handle(), ctx.clone());
+        state.watch_cache(&runtime.handle(), ctx.clone());
+        state.watch_rumble(&runtime.handle(), ctx.clone());
+        state.fill_icons(&runtime.handle(), ctx.clone());
+        let notifications = Notifications::default();
+
+        // redraw whenever the options change, even if the change came from the worker
+        {
+            let changes = options.subscribe();
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                while changes.recv().is_ok() {
+                    ctx.request_repaint();
+                }
+            });
+        }
 
         // make the worker that runs async functions in a background thread
         {
-            let cache = cache.clone();
+            let state = state.clone();
             let handle = runtime.handle();
-            let errors = runtime_errors.clone();
+            let notifications = notifications.clone();
+            let tasks = tasks.clone();
+            let ctx = ctx.clone();
 
             handle.spawn(async move {
-                while let Some(cmd) = cmd_rx.recv().await {
-                    let mut cache = cache.write().await;
+                while let Some(QueuedCommand { id, command: cmd, cancel }) = cmd_rx.recv().await {
+                    // cancelled while it was waiting
+                    if cancel.is_cancelled() {
+                        tasks.set_state(id, TaskState::Cancelled);
+                        continue;
+                    }
+                    tasks.set_state(id, TaskState::Running);
+                    ctx.request_repaint();
+                    let mut cache = state.lock_cache().await;
+                    cache.set_cancel_token(cancel);
                     let config = &Config::new();
-                    match cmd {
-                        AppCommand::UpdateMod(mod_to_update) => {
-                            println!("updating mod!");
-                            if let Err(e) = cache.update_mod(config, &mod_to_update).await {
-                                let error_msg = format!("Update mod error: {e}");
-                                println!("{}", &error_msg);
-                                errors.lock().unwrap().push_back(error_msg);
-                            }
-                        }
-                        AppCommand::CacheModByID(id, version) => {
-                            if let Err(e) = cache
-                                .cache_mod_by_mod_id(&id.to_string(), version.as_ref())
-                                .await
-                            {
-                                let error_msg = format!("Cache mod error: {e}");
-                                println!("{}", &error_msg);
-                                errors.lock().unwrap().push_back(error_msg);
-                            }
-                        }
-                        AppCommand::UpdateAllMods => {
-                            if let Err(e) = cache.update_all_mods(config).await {
-                                let error_msg = format!("Update all mods error: {e}");
-                                println!("{}", &error_msg);
-                                errors.lock().unwrap().push_back(error_msg);
-                            }
-                        }
-                        AppCommand::SyncModsToRumble => {
-                            if let Err(e) = cache.sync_all_mods_to_rumble(config).await {
-                                let error_msg = format!("Sync mods error: {e}");
-                                println!("{}", &error_msg);
-                                errors.lock().unwrap().push_back(error_msg);
-                            }
-                        }
+                    let result = run_command(&mut cache, config, cmd.clone())
+                        .await
+                        .map_err(CommandError::from);
+                    // even failed commands can leave something behind, so always publish
+                    if let Err(e) = state.publish(&mut cache) {
+                        notifications.push(Notification::warning("Refresh mods", e.to_string()));
+                    }
+                    drop(cache);
+                    if let Ok(CommandOutcome::DriftChecked(report)) = &result {
+                        state.set_drift(report.clone());
                     }
+                    let (state, notification) = match &result {
+                        Ok(outcome) if outcome.is_partial() => (
+                            TaskState::Succeeded,
+                            Notification::warning(cmd.source(), outcome.to_string()),
+                        ),
+                        Ok(outcome) => (
+                            TaskState::Succeeded,
+                            Notification::success(cmd.source(), outcome.to_string()),
+                        ),
+                        Err(CommandError::Cancelled) => {
+                            (TaskState::Cancelled, Notification::warning(cmd.source(), "Cancelled"))
+                        }
+                        Err(e) => (
+                            TaskState::Failed,
+                            Notification::error(cmd.source(), e.to_string())
+                                .with_action(NotificationAction::Retry(Box::new(cmd.clone()))),
+                        ),
+                    };
+                    tasks.set_state(id, state);
+                    notifications.push(notification.with_mod(cmd.mod_id()));
+                    let _ = responses.send(CommandResponse {
+                        id,
+                        command: cmd,
+                        result,
+                    });
+                    ctx.request_repaint();
                 }
             });
         }
 
         Self {
-            cache,
-            tabs: MyTabs::new(mods, runtime_commands.clone(), runtime_errors.clone()),
+            tabs: MyTabs::new(
+                state.clone(),
+                tasks,
+                response_rx,
+                notifications.clone(),
+                AppUpdatePanel::new(runtime.handle(), ctx.clone(), notifications.clone()),
+            ),
+            state,
+            last_options_check: Instant::now(),
+            rules_error: None,
             handle: runtime.handle(),
             runtime,
-            runtime_commands,
-            runtime_errors,
+            notifications,
         }
     }
 }
@@ -169,46 +225,97 @@ impl Drop for RuntimeGuard {
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         egui_extras::install_image_loaders(ctx);
+        // pick up changes made by other processes (like the background updater)
+        if self.last_options_check.elapsed() > OPTIONS_RELOAD_INTERVAL {
+            self.last_options_check = Instant::now();
+            if let Err(e) = self.state.options().reload_if_changed() {
+                self.notifications.push(Notification::warning(
+                    "Reload mod options",
+                    e.to_string(),
+                ));
+            }
+        }
+        let rules_error = self.state.snapshot().cache.rules_error().map(str::to_string);
+        if rules_error != self.rules_error {
+            if let Some(e) = &rules_error {
+                self.notifications.push(Notification::error(
+                    "Rule file",
+                    format!("{e}. Nothing will be installed or synced until it's fixed"),
+                ));
+            }
+            self.rules_error = rules_error;
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
             self.tabs.ui(ui);
         });
     }
 }
+/// How often to check if another process changed the mod options
+const OPTIONS_RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
 pub type TabResult = Result<Option<AppCommand>, color_eyre::eyre::Report>;
 
+/// A mod's icon from the icon cache, or a placeholder until it's been cached
+pub fn mod_icon(icons: &IconCache, version: &Version) -> egui::Image<'static> {
+    let image = match icons.get(version) {
+        Some(path) => egui::Image::new(format!("file://{}", path.display())),
+        None => egui::Image::new(egui::include_image!("./icons/package.svg")),
+    };
+    image.fit_to_exact_size(egui::Vec2::splat(THUMBNAIL_SIZE as f32))
+}
+
 pub enum CustomTab {
-    ThunderstoreBrowser,
-    LocalModList(LocalModsTab),
-    Settings(Config),
+    ThunderstoreBrowser(Box<ThunderstoreBrowserTab>),
+    LocalModList(Box<LocalModsTab>),
+    Settings(Box<SettingsTab>),
+    Logs(LogsTab),
 }
 
 /// This custom tab viewer delegates each tab's UI to the respective module.
 struct MyTabViewer {
-    error_popup: Option<String>,
-    runtime_commands: mpsc::UnboundedSender<AppCommand>,
+    notifications: Notifications,
+    tasks: TaskQueue,
 }
 
 impl MyTabViewer {
-    fn new(runtime_commands: mpsc::UnboundedSender<AppCommand>) -> Self {
+    fn new(tasks: TaskQueue, notifications: Notifications) -> Self {
         Self {
-            error_popup: None,
-            runtime_commands,
+            notifications,
+            tasks,
         }
     }
+}
 
-    fn show_error_popup(&mut self, ui: &mut Ui) {
-        if let Some(error_message) = &self.error_popup.clone() {
-            // Show a popup window with the error message
-            egui::Window::new("Error")
-                .collapsible(false)
-                .resizable(false)
-                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
-                .show(ui.ctx(), |ui| {
-                    ui.label(error_message);
-                    if ui.button("OK").clicked() {
-                        self.error_popup = None;
-                    }
-                });
+impl CustomTab {
+    fn name(&self) -> &'static str {
+        match self {
+            CustomTab::ThunderstoreBrowser(_) => "Mod Browser",
+            CustomTab::LocalModList(_) => "Mods",
+            CustomTab::Settings(_) => "Settings",
+            CustomTab::Logs(_) => "Logs",
+        }
+    }
+}
+
+// there's only ever one of each tab, so tabs are equal if they're the same kind
+impl PartialEq for CustomTab {
+    fn eq(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+impl CustomTab {
+    /// Lets a tab keep track of the commands it started, by ID
+    fn command_submitted(&mut self, id: u64, command: AppCommand) {
+        if let CustomTab::LocalModList(tab) = self {
+            tab.command_submitted(id, command);
+        }
+    }
+
+    /// Called with every finished command, including ones the tab didn't start
+    fn handle_response(&mut self, response: &CommandResponse) {
+        if let CustomTab::LocalModList(tab) = self {
+            tab.handle_response(response);
         }
     }
 }
@@ -217,25 +324,23 @@ impl TabViewer for MyTabViewer {
     type Tab = CustomTab;
 
     fn title(&mut self, tab: &mut Self::Tab) -> WidgetText {
-        match tab {
-            CustomTab::ThunderstoreBrowser => "Mod Browser".into(),
-            CustomTab::LocalModList(_) => "Mods".into(),
-            CustomTab::Settings(_) => "Settings".into(),
-        }
+        tab.name().into()
     }
 
     fn ui(&mut self, ui: &mut Ui, tab: &mut Self::Tab) {
-        self.show_error_popup(ui);
         let result = match tab {
-            CustomTab::ThunderstoreBrowser => draw_thunderstore_browser(ui),
+            CustomTab::ThunderstoreBrowser(browser) => browser.ui(ui),
             CustomTab::LocalModList(tab) => tab.ui(ui),
-            CustomTab::Settings(config) => draw_settings_ui(ui, config),
+            CustomTab::Settings(settings) => draw_settings_ui(ui, settings),
+            CustomTab::Logs(logs) => logs.ui(ui),
         };
-        if let Err(err) = &result {
-            self.error_popup = Some(format!("Error: {}", err));
-        }
-        if let Ok(Some(cmd)) = result {
-            let _ = self.runtime_commands.send(cmd);
+        match result {
+            Err(err) => self.notifications.push(Notification::error(tab.name(), err.to_string())),
+            Ok(Some(cmd)) => {
+                let id = self.tasks.submit(cmd.clone());
+                tab.command_submitted(id, cmd);
+            }
+            Ok(None) => {}
         }
     }
 }
@@ -243,36 +348,54 @@ impl TabViewer for MyTabViewer {
 struct MyTabs {
     dock_state: DockState<CustomTab>,
     tab_viewer: MyTabViewer,
-    runtime_errors: Arc<Mutex<VecDeque<String>>>,
+    notification_panel: NotificationPanel,
+    task_panel: TaskPanel,
+    app_update: AppUpdatePanel,
+    /// Results of finished commands, from the worker
+    responses: Receiver<CommandResponse>,
+    /// Used to show mod names in notifications and tasks
+    state: AppState,
 }
 
 impl MyTabs {
     pub fn new(
-        thunderstore_mod_list: ModList,
-        runtime_commands: mpsc::UnboundedSender<AppCommand>,
-        runtime_errors: Arc<Mutex<VecDeque<String>>>,
+        state: AppState,
+        tasks: TaskQueue,
+        responses: Receiver<CommandResponse>,
+        notifications: Notifications,
+        app_update: AppUpdatePanel,
     ) -> Self {
         // Create initial tabs using the mod list.
-        let local_options = LocalModOptions::new(&Config::new());
         let tabs = vec![
-            CustomTab::LocalModList(LocalModsTab::new(&thunderstore_mod_list, local_options)),
-            CustomTab::ThunderstoreBrowser,
-            CustomTab::Settings(Config::new()),
+            CustomTab::LocalModList(Box::new(LocalModsTab::new(state.clone(), notifications.clone()))),
+            CustomTab::ThunderstoreBrowser(Box::new(ThunderstoreBrowserTab::new(state.clone()))),
+            CustomTab::Settings(Box::new(SettingsTab::new(Config::new()))),
+            CustomTab::Logs(LogsTab::new()),
         ];
         let dock_state = DockState::new(tabs);
         Self {
             dock_state,
-            tab_viewer: MyTabViewer::new(runtime_commands),
-            runtime_errors,
+            tab_viewer: MyTabViewer::new(tasks, notifications),
+            notification_panel: NotificationPanel::default(),
+            task_panel: TaskPanel::default(),
+            app_update,
+            responses,
+            state,
         }
     }
 
     fn ui(&mut self, ui: &mut Ui) {
-        // make vertical layout to fit tabs and error messages
+        while let Ok(response) = self.responses.try_recv() {
+            for (_, tab) in self.dock_state.iter_all_tabs_mut() {
+                tab.handle_response(&response);
+            }
+        }
+        let snapshot = self.state.snapshot();
+        // make vertical layout to fit tabs and notifications
         egui::ScrollArea::vertical().show(ui, |ui| {
             let available_height = ui.available_height();
-            // Leave some space for the label
-            let dock_height = available_height - 20.0;
+            // Leave some space for the notification bar
+            let dock_height = available_height - 24.0;
             ui.allocate_ui(
                 eframe::egui::Vec2::new(ui.available_width(), dock_height),
                 |ui| {
@@ -281,15 +404,30 @@ impl MyTabs {
                         .show_inside(ui, &mut self.tab_viewer);
                 },
             );
-            let mut error_queue = self.runtime_errors.lock().unwrap();
-            if !error_queue.is_empty() {
-                let error_message = error_queue.front().unwrap();
-                ui.label(egui::RichText::new(error_message).color(egui::Color32::RED));
-
-                // Optional: Add a button to clear the current error
-                if ui.button("Clear").clicked() {
-                    error_queue.pop_front();
+            let action = ui
+                .horizontal(|ui| {
+                    self.task_panel
+                        .ui(ui, &self.tab_viewer.tasks, snapshot.index());
+                    ui.separator();
+                    self.app_update.ui(ui);
+                    ui.separator();
+                    self.notification_panel.ui(
+                        ui,
+                        &self.tab_viewer.notifications,
+                        snapshot.index(),
+                    )
+                })
+                .inner;
+            match action {
+                Some(NotificationAction::Retry(cmd)) => {
+                    self.tab_viewer.tasks.submit(*cmd);
+                }
+                Some(NotificationAction::OpenLog) => {
+                    if let Some(location) = self.dock_state.find_tab(&CustomTab::Logs(LogsTab::new())) {
+                        self.dock_state.set_active_tab(location);
+                    }
                 }
+                None => {}
             }
         });
     }
@@ -0,0 +1,230 @@
+// Everything the user should know about that happened in the background, good or bad.
+// The worker and the tabs push notifications, the bar at the bottom of the window shows them
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use eframe::egui::{self, Color32, RichText, Ui};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::history::format_time_ago;
+use crate::thunderstore::ModList;
+
+use super::AppCommand;
+
+/// Old notifications get dropped past this
+const MAX_NOTIFICATIONS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Success,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn color(&self) -> Color32 {
+        match self {
+            Severity::Success => Color32::GREEN,
+            Severity::Warning => Color32::YELLOW,
+            Severity::Error => Color32::RED,
+        }
+    }
+}
+
+/// Buttons shown on a notification
+#[derive(Clone)]
+pub enum NotificationAction {
+    /// Runs the command that failed again
+    Retry(Box<AppCommand>),
+    /// Switches to the Logs tab
+    OpenLog,
+}
+
+#[derive(Clone)]
+pub struct Notification {
+    pub severity: Severity,
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    /// The mod this is about, if any
+    pub mod_id: Option<Uuid>,
+    /// What was being done, like "Update mod"
+    pub source: String,
+    pub message: String,
+    pub actions: Vec<NotificationAction>,
+    /// How many times in a row this exact notification came in
+    pub count: usize,
+}
+
+impl Notification {
+    fn new(severity: Severity, source: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or_default(),
+            mod_id: None,
+            source: source.into(),
+            message: message.into(),
+            actions: vec![],
+            count: 1,
+        }
+    }
+
+    pub fn success(source: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(Severity::Success, source, message)
+    }
+
+    pub fn warning(source: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, source, message)
+    }
+
+    pub fn error(source: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, source, message).with_action(NotificationAction::OpenLog)
+    }
+
+    pub fn with_mod(mut self, mod_id: Option<Uuid>) -> Self {
+        self.mod_id = mod_id;
+        self
+    }
+
+    pub fn with_action(mut self, action: NotificationAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    fn is_same_as(&self, other: &Notification) -> bool {
+        self.severity == other.severity
+            && self.source == other.source
+            && self.message == other.message
+            && self.mod_id == other.mod_id
+    }
+}
+
+/// Shared list of notifications, newest last. Cheap to clone
+#[derive(Clone, Default)]
+pub struct Notifications {
+    inner: Arc<Mutex<VecDeque<Notification>>>,
+}
+
+impl Notifications {
+    /// Adds a notification and logs it. Repeats of the latest notification are merged into it,
+    /// so an error that happens every frame doesn't flood the list (or the log)
+    pub fn push(&self, notification: Notification) {
+        let mut list = self.inner.lock().unwrap();
+        if let Some(latest) = list.back_mut()
+            && latest.is_same_as(&notification)
+        {
+            latest.count += 1;
+            latest.timestamp = notification.timestamp;
+            return;
+        }
+        match notification.severity {
+            Severity::Success => info!("{}: {}", notification.source, notification.message),
+            Severity::Warning => warn!("{}: {}", notification.source, notification.message),
+            Severity::Error => error!("{}: {}", notification.source, notification.message),
+        }
+        if list.len() >= MAX_NOTIFICATIONS {
+            list.pop_front();
+        }
+        list.push_back(notification);
+    }
+
+    pub fn snapshot(&self) -> Vec<Notification> {
+        self.inner.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().clear();
+    }
+}
+
+/// State for the notification bar at the bottom of the window
+#[derive(Default)]
+pub struct NotificationPanel {
+    expanded: bool,
+    /// Number of notifications the user has already seen
+    seen: usize,
+}
+
+impl NotificationPanel {
+    /// Draws the bar (and the full list if it's expanded). Returns the action the user clicked, if any
+    pub fn ui(&mut self, ui: &mut Ui, notifications: &Notifications, mod_list: &ModList) -> Option<NotificationAction> {
+        let list = notifications.snapshot();
+        if self.expanded {
+            self.seen = list.len();
+        }
+        let mut clicked = None;
+        ui.horizontal(|ui| {
+            let unseen = list.len().saturating_sub(self.seen);
+            let toggle_text = if unseen > 0 {
+                format!("Notifications ({unseen} new)")
+            } else {
+                "Notifications".to_string()
+            };
+            if ui.selectable_label(self.expanded, toggle_text).clicked() {
+                self.expanded = !self.expanded;
+            }
+            if let Some(latest) = list.last() {
+                draw_summary(ui, latest, mod_list);
+            }
+        });
+        if !self.expanded {
+            return None;
+        }
+        egui::Window::new("Notifications")
+            .open(&mut self.expanded)
+            .default_width(500.0)
+            .show(ui.ctx(), |ui| {
+                if list.is_empty() {
+                    ui.label("Nothing here yet.");
+                    return;
+                }
+                if ui.button("Clear All").clicked() {
+                    notifications.clear();
+                }
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for notification in list.iter().rev() {
+                        ui.horizontal(|ui| {
+                            draw_summary(ui, notification, mod_list);
+                            ui.weak(format_time_ago(notification.timestamp));
+                            for action in &notification.actions {
+                                let text = match action {
+                                    NotificationAction::Retry(_) => "Retry",
+                                    NotificationAction::OpenLog => "Open Log",
+                                };
+                                if ui.small_button(text).clicked() {
+                                    clicked = Some(action.clone());
+                                }
+                            }
+                        });
+                        ui.separator();
+                    }
+                });
+            });
+        clicked
+    }
+}
+
+/// `✔ Add Mod [SomeMod]: Added SomeMod (x2)`
+fn draw_summary(ui: &mut Ui, notification: &Notification, mod_list: &ModList) {
+    let icon = match notification.severity {
+        Severity::Success => "✔",
+        Severity::Warning => "⚠",
+        Severity::Error => "✖",
+    };
+    ui.label(RichText::new(icon).color(notification.severity.color()));
+    let mod_name = notification
+        .mod_id
+        .and_then(|id| mod_list.mods.iter().find(|x| x.uuid == id))
+        .map(|x| format!(" [{}]", x.name))
+        .unwrap_or_default();
+    ui.strong(format!("{}{mod_name}:", notification.source));
+    ui.label(&notification.message);
+    if notification.count > 1 {
+        ui.weak(format!("(x{})", notification.count));
+    }
+}
@@ -4,21 +4,49 @@ use std::{
 };
 
 use color_eyre::eyre::{Result, eyre};
-use eframe::egui::Ui;
+use eframe::egui::{self, Ui};
 
-use crate::config_and_such::Config;
+use crate::config_and_such::{Config, UpdateChannel, UpdatePolicy};
 
-use super::TabResult;
+use super::{AppCommand, TabResult};
+
+/// State for the Settings tab
+pub struct SettingsTab {
+    config: Config,
+    /// Path typed into the offline bundle box
+    bundle_path: String,
+    export_all_versions: bool,
+    /// Last checked Rumble directory and what the check said, so the folder isn't read every frame
+    rumble_exe_check: Option<(String, String)>,
+}
+
+impl SettingsTab {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            bundle_path: "rumm-bundle.zip".to_string(),
+            export_all_versions: false,
+            rumble_exe_check: None,
+        }
+    }
+}
 
 enum ChangeType {
     RumbleDirectory(PathBuf),
     ModCacheDirectory(PathBuf),
     ConfigFile(PathBuf),
-    ShouldAutoUpdate(bool),
+    DefaultUpdatePolicy(UpdatePolicy),
+    KeepPreviousVersions(Option<usize>),
+    EvictUnusedAfterDays(Option<u64>),
+    RulesFile(PathBuf),
+    UpdateChannel(UpdateChannel),
+    ReleaseSource(String),
 }
 
-pub fn draw_settings_ui(ui: &mut Ui, config: &mut Config) -> TabResult {
+pub fn draw_settings_ui(ui: &mut Ui, settings: &mut SettingsTab) -> TabResult {
+    let config = &mut settings.config;
     let mut changes = vec![];
+    let mut command = None;
 
     ui.vertical(|ui| -> Result<()> {
         // Rumble Directory
@@ -35,14 +63,20 @@ pub fn draw_settings_ui(ui: &mut Ui, config: &mut Config) -> TabResult {
             if rumble_directory != original_rumble_directory {
                 changes.push(ChangeType::RumbleDirectory(rumble_directory.clone().into()));
             }
-            let result = check_for_rumble_exe(&PathBuf::from_str(&rumble_directory)?);
-            // If error, return error string. If Ok(false) return a static string, if Ok(true)
-            let result_text = match result {
-                Err(e) => format!("Error: {}", e.to_string()),
-                Ok(false) => "Could not find Rumble executable in file!".into(),
-                Ok(true) => "Rumble executable found!".into(),
-            };
-            ui.label(result_text);
+            let rumble_exe_check = &mut settings.rumble_exe_check;
+            if rumble_exe_check.as_ref().is_none_or(|(path, _)| *path != rumble_directory) {
+                let result = check_for_rumble_exe(&PathBuf::from_str(&rumble_directory)?);
+                // If error, return error string. If Ok(false) return a static string, if Ok(true)
+                let result_text = match result {
+                    Err(e) => format!("Error: {e}"),
+                    Ok(false) => "Could not find Rumble executable in file!".into(),
+                    Ok(true) => "Rumble executable found!".into(),
+                };
+                *rumble_exe_check = Some((rumble_directory.clone(), result_text));
+            }
+            if let Some((_, result_text)) = rumble_exe_check {
+                ui.label(result_text.as_str());
+            }
             Ok(())
         });
         // Mod Cahce Directory
@@ -63,11 +97,100 @@ pub fn draw_settings_ui(ui: &mut Ui, config: &mut Config) -> TabResult {
             };
             Ok(())
         });
+        // Blocked packages, incompatible mods and version ranges, see `rules.rs`
+        ui.horizontal(|ui| {
+            ui.label("Rules File: ");
+            let original_rules_file = config.rules_file.to_string_lossy().to_string();
+            let mut rules_file = original_rules_file.clone();
+            ui.text_edit_singleline(&mut rules_file)
+                .on_hover_text("Packages to hide or block, mods that don't work together, and versions to stay within");
+            if rules_file != original_rules_file {
+                changes.push(ChangeType::RulesFile(rules_file.into()));
+            }
+        });
+        // Cache cleanup rules, used by "Clean Up Cache" in the Mods tab
+        ui.horizontal(|ui| {
+            let original = config.cache_retention.keep_previous_versions;
+            let mut enabled = original.is_some();
+            let mut count = original.unwrap_or(2);
+            ui.checkbox(&mut enabled, "Only keep the selected version plus");
+            ui.add_enabled(enabled, egui::DragValue::new(&mut count).range(0..=100));
+            ui.label("older versions");
+            let new = enabled.then_some(count);
+            if new != original {
+                changes.push(ChangeType::KeepPreviousVersions(new));
+            }
+        });
+        ui.horizontal(|ui| {
+            let original = config.cache_retention.evict_unused_after_days;
+            let mut enabled = original.is_some();
+            let mut days = original.unwrap_or(30);
+            ui.checkbox(&mut enabled, "Remove versions not used in");
+            ui.add_enabled(enabled, egui::DragValue::new(&mut days).range(1..=3650));
+            ui.label("days");
+            let new = enabled.then_some(days);
+            if new != original {
+                changes.push(ChangeType::EvictUnusedAfterDays(new));
+            }
+        });
+        // mods can override this in the Mods tab
+        ui.horizontal(|ui| {
+            ui.label("Mod Updates: ");
+            let mut policy = config.default_update_policy;
+            for option in UpdatePolicy::ALL {
+                ui.radio_value(&mut policy, option, option.to_string());
+            }
+            if policy != config.default_update_policy {
+                changes.push(ChangeType::DefaultUpdatePolicy(policy));
+            }
+        })
+        .response
+        .on_hover_text("What Update All and the background updater do with mods that don't pick their own");
+        // Updates for rumm itself, see `updater.rs`
+        ui.horizontal(|ui| {
+            ui.label("Update Channel: ");
+            let mut channel = config.update_channel;
+            ui.radio_value(&mut channel, UpdateChannel::Stable, "Stable");
+            ui.radio_value(&mut channel, UpdateChannel::Prerelease, "Prerelease");
+            if channel != config.update_channel {
+                changes.push(ChangeType::UpdateChannel(channel));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Release Source: ");
+            let mut release_source = config.release_source.clone();
+            ui.text_edit_singleline(&mut release_source)
+                .on_hover_text("Where rumm checks for new versions of itself. Anything serving GitHub's releases JSON works");
+            if release_source != config.release_source {
+                changes.push(ChangeType::ReleaseSource(release_source));
+            }
+        });
+        // Offline bundles, for setting up PCs without internet
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Offline Bundle: ");
+            ui.text_edit_singleline(&mut settings.bundle_path);
+            ui.checkbox(&mut settings.export_all_versions, "Include all cached versions")
+                .on_hover_text("Otherwise only the selected version of each mod is included");
+            if ui.button("Export").clicked() {
+                command = Some(AppCommand::ExportBundle(
+                    settings.bundle_path.clone().into(),
+                    settings.export_all_versions,
+                ));
+            }
+            if ui
+                .button("Import")
+                .on_hover_text("Adds the bundle's mods to the cache and applies its mod options")
+                .clicked()
+            {
+                command = Some(AppCommand::ImportBundle(settings.bundle_path.clone().into()));
+            }
+        });
         Ok(())
     });
 
     apply_changes(config, changes)?;
-    Ok(None)
+    Ok(command)
 }
 
 fn check_for_rumble_exe(path: &Path) -> Result<bool> {
@@ -82,7 +205,12 @@ fn apply_changes(config: &mut Config, changes: Vec<ChangeType>) -> Result<()> {
             ChangeType::RumbleDirectory(file) => config.rumble_directory = file,
             ChangeType::ModCacheDirectory(file) => config.mod_cache_directory = file,
             ChangeType::ConfigFile(file) => config.config_file = file,
-            ChangeType::ShouldAutoUpdate(x) => config.should_auto_update = x,
+            ChangeType::DefaultUpdatePolicy(x) => config.default_update_policy = x,
+            ChangeType::KeepPreviousVersions(x) => config.cache_retention.keep_previous_versions = x,
+            ChangeType::EvictUnusedAfterDays(x) => config.cache_retention.evict_unused_after_days = x,
+            ChangeType::RulesFile(file) => config.rules_file = file,
+            ChangeType::UpdateChannel(x) => config.update_channel = x,
+            ChangeType::ReleaseSource(x) => config.release_source = x,
         }
     }
     config.save_to_file()
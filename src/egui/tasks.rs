@@ -0,0 +1,232 @@
+// Every `AppCommand` runs as a task. Tasks wait in a queue for the worker, which runs them one at a time.
+// The task panel lists them, and any task that hasn't finished can be cancelled
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use eframe::egui::{self, Color32, RichText, Ui};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::thunderstore::ModList;
+
+use super::AppCommand;
+
+/// Finished tasks past this get dropped from the list
+const MAX_FINISHED_TASKS: usize = 50;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskState {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl TaskState {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, TaskState::Pending | TaskState::Running)
+    }
+}
+
+#[derive(Clone)]
+pub struct Task {
+    pub id: u64,
+    /// What the task does, like "Update mod"
+    pub label: &'static str,
+    pub mod_id: Option<Uuid>,
+    pub state: TaskState,
+    queued_at: Instant,
+    started_at: Option<Instant>,
+    finished_at: Option<Instant>,
+    cancel: CancellationToken,
+}
+
+impl Task {
+    /// Time spent running so far, or in total if it's finished
+    pub fn elapsed(&self) -> Option<Duration> {
+        let started_at = self.started_at?;
+        Some(self.finished_at.unwrap_or_else(Instant::now) - started_at)
+    }
+}
+
+/// What the worker gets for each task
+pub struct QueuedCommand {
+    pub id: u64,
+    pub command: AppCommand,
+    pub cancel: CancellationToken,
+}
+
+/// The list of tasks, shared between the UI and the worker. Cheap to clone
+#[derive(Clone)]
+pub struct TaskQueue {
+    tasks: Arc<Mutex<Vec<Task>>>,
+    next_id: Arc<AtomicU64>,
+    worker: UnboundedSender<QueuedCommand>,
+}
+
+impl TaskQueue {
+    pub fn new(worker: UnboundedSender<QueuedCommand>) -> Self {
+        Self {
+            tasks: Arc::new(Mutex::new(vec![])),
+            next_id: Arc::new(AtomicU64::new(0)),
+            worker,
+        }
+    }
+
+    /// Queues a command for the worker
+    pub fn submit(&self, command: AppCommand) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let cancel = CancellationToken::new();
+        self.tasks.lock().unwrap().push(Task {
+            id,
+            label: command.source(),
+            mod_id: command.mod_id(),
+            state: TaskState::Pending,
+            queued_at: Instant::now(),
+            started_at: None,
+            finished_at: None,
+            cancel: cancel.clone(),
+        });
+        let _ = self.worker.send(QueuedCommand { id, command, cancel });
+        id
+    }
+
+    /// Pending tasks are skipped, running ones stop at the next point where it's safe to
+    pub fn cancel(&self, id: u64) {
+        let mut tasks = self.tasks.lock().unwrap();
+        if let Some(task) = tasks.iter_mut().find(|x| x.id == id) {
+            task.cancel.cancel();
+            // the worker will skip it when it gets there, but show it as cancelled right away
+            if task.state == TaskState::Pending {
+                task.state = TaskState::Cancelled;
+                task.finished_at = Some(Instant::now());
+            }
+        }
+    }
+
+    pub fn set_state(&self, id: u64, state: TaskState) {
+        let mut tasks = self.tasks.lock().unwrap();
+        let Some(task) = tasks.iter_mut().find(|x| x.id == id) else {
+            return;
+        };
+        match state {
+            TaskState::Running => task.started_at = Some(Instant::now()),
+            ref x if x.is_finished() => task.finished_at = Some(Instant::now()),
+            _ => {}
+        }
+        task.state = state;
+        // forget the oldest finished tasks
+        let finished = tasks.iter().filter(|x| x.state.is_finished()).count();
+        if finished > MAX_FINISHED_TASKS {
+            let mut to_remove = finished - MAX_FINISHED_TASKS;
+            tasks.retain(|x| {
+                if to_remove > 0 && x.state.is_finished() {
+                    to_remove -= 1;
+                    return false;
+                }
+                true
+            });
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<Task> {
+        self.tasks.lock().unwrap().clone()
+    }
+
+    pub fn clear_finished(&self) {
+        self.tasks.lock().unwrap().retain(|x| !x.state.is_finished());
+    }
+}
+
+/// State for the task panel
+#[derive(Default)]
+pub struct TaskPanel {
+    expanded: bool,
+}
+
+impl TaskPanel {
+    /// Draws the "Tasks" toggle in the bottom bar, plus the task window if it's open
+    pub fn ui(&mut self, ui: &mut Ui, queue: &TaskQueue, mod_list: &ModList) {
+        let tasks = queue.snapshot();
+        let active = tasks.iter().filter(|x| !x.state.is_finished()).count();
+        let toggle_text = if active > 0 {
+            format!("Tasks ({active})")
+        } else {
+            "Tasks".to_string()
+        };
+        if ui.selectable_label(self.expanded, toggle_text).clicked() {
+            self.expanded = !self.expanded;
+        }
+        if active > 0 {
+            ui.spinner();
+            // keep the elapsed times ticking
+            ui.ctx().request_repaint_after(Duration::from_millis(250));
+        }
+        if !self.expanded {
+            return;
+        }
+        egui::Window::new("Tasks")
+            .open(&mut self.expanded)
+            .default_width(450.0)
+            .show(ui.ctx(), |ui| {
+                if tasks.is_empty() {
+                    ui.label("Nothing has been queued yet.");
+                    return;
+                }
+                if ui.button("Clear Finished").clicked() {
+                    queue.clear_finished();
+                }
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    egui::Grid::new("Task Grid").striped(true).show(ui, |ui| {
+                        // running first, then pending in the order they'll run, then the most recently finished
+                        let mut sorted: Vec<&Task> = tasks.iter().collect();
+                        sorted.sort_by_key(|x| match x.state {
+                            TaskState::Running => (0, 0),
+                            TaskState::Pending => (1, x.id as i64),
+                            _ => (2, -(x.id as i64)),
+                        });
+                        for task in sorted {
+                            let mod_name = task
+                                .mod_id
+                                .and_then(|id| mod_list.mods.iter().find(|x| x.uuid == id))
+                                .map(|x| format!(" [{}]", x.name))
+                                .unwrap_or_default();
+                            ui.label(format!("{}{mod_name}", task.label));
+                            draw_state(ui, task);
+                            match task.elapsed() {
+                                Some(elapsed) => ui.weak(format!("{:.1}s", elapsed.as_secs_f32())),
+                                None if task.state.is_finished() => ui.weak(""),
+                                None => ui.weak(format!(
+                                    "waiting {}s",
+                                    task.queued_at.elapsed().as_secs()
+                                )),
+                            };
+                            if task.state.is_finished() {
+                                ui.label("");
+                            } else if task.cancel.is_cancelled() {
+                                ui.weak("Cancelling…");
+                            } else if ui.small_button("Cancel").clicked() {
+                                queue.cancel(task.id);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+    }
+}
+
+fn draw_state(ui: &mut Ui, task: &Task) {
+    let (text, color) = match task.state {
+        TaskState::Pending => ("Pending", ui.visuals().weak_text_color()),
+        TaskState::Running => ("Running", ui.visuals().text_color()),
+        TaskState::Succeeded => ("Done", Color32::GREEN),
+        TaskState::Failed => ("Failed", Color32::RED),
+        TaskState::Cancelled => ("Cancelled", Color32::YELLOW),
+    };
+    ui.label(RichText::new(text).color(color));
+}
@@ -1,80 +1,214 @@
 // src/gui/ThunderstoreBrowser.rs
 
-use std::{path::PathBuf, str::FromStr};
+use std::sync::Arc;
 
 use crate::{
     config_and_such::{Config, SortType},
-    thunderstore::ModList,
+    icon_cache::{IconCache, THUMBNAIL_SIZE},
+    rules::{IncompatibilityRule, PackageRule},
+    thunderstore::{Mod, ModList},
 };
 use eframe::egui::{self, ComboBox, Ui};
 
-use super::{AppCommand, TabResult};
+use super::app_state::{AppSnapshot, AppState};
+use super::dependency_ui::DependencyWindow;
+use super::{AppCommand, TabResult, mod_icon};
 
-/// Renders the Thunderstore Browser tab UI
-pub fn draw_thunderstore_browser(ui: &mut Ui) -> TabResult {
-    let mut mod_list = ModList::new(PathBuf::from_str("config/thunderstore-mods.json").unwrap())?;
-    let combo_box = ComboBox::from_id_salt("test");
-    let mut config = Config::new();
-    let mut sort = config.thunderstore_browser_sort.clone();
-    let sorted_mod_list = mod_list.sort(&sort).clone();
-    let mut selected_sort = String::from(config.thunderstore_browser_sort);
-    combo_box
-        .selected_text(String::from(sort.clone()))
-        .show_ui(ui, |ui| {
-            let sort_options = vec![
-                SortType::Alphabetically,
-                SortType::UpdateDate,
-                SortType::ReleaseDate,
-            ];
-            for option in sort_options.clone() {
-                ui.selectable_value(
-                    &mut selected_sort,
-                    option.clone().into(),
-                    String::from(option),
-                );
+/// State for the Mod Browser tab
+pub struct ThunderstoreBrowserTab {
+    state: AppState,
+    /// Loaded once, and saved to the config whenever it changes
+    sort: SortType,
+    /// Only mods with this in their name are shown
+    search: String,
+    /// What the list shows, rebuilt only when the index, sort or search changes
+    list: Option<BrowserList>,
+    /// A mod that breaks a rule with something already enabled, waiting for the user to confirm
+    conflict_confirmation: Option<(Mod, Vec<IncompatibilityRule>)>,
+    /// Open from a mod's Dependencies button
+    dependency_window: Option<DependencyWindow>,
+}
+
+/// The index with hidden mods left out, searched and sorted, plus what it was made from
+struct BrowserList {
+    /// Snapshots come with every cache change, but the index is only swapped out when Thunderstore is refreshed
+    index: Arc<ModList>,
+    /// The rules that decide which mods are hidden
+    package_rules: Vec<PackageRule>,
+    sort: SortType,
+    search: String,
+    mods: Vec<Mod>,
+}
+
+impl BrowserList {
+    fn new(snapshot: &AppSnapshot, sort: &SortType, search: &str) -> Self {
+        let search_lowercase = search.to_lowercase();
+        let rules = snapshot.cache.rules();
+        let mut list = ModList {
+            mods: snapshot
+                .index()
+                .mods
+                .iter()
+                // sorting needs at least one version
+                .filter(|x| !x.versions.is_empty() && !rules.is_hidden(x))
+                .filter(|x| x.name.to_lowercase().contains(&search_lowercase))
+                .cloned()
+                .collect(),
+        };
+        list.sort_self(sort);
+        Self {
+            index: snapshot.index().clone(),
+            package_rules: rules.packages.clone(),
+            sort: sort.clone(),
+            search: search.to_string(),
+            mods: list.mods,
+        }
+    }
+
+    fn is_stale(&self, snapshot: &AppSnapshot, sort: &SortType, search: &str) -> bool {
+        !Arc::ptr_eq(&self.index, snapshot.index())
+            || self.package_rules != snapshot.cache.rules().packages
+            || self.sort != *sort
+            || self.search != search
+    }
+}
+
+impl ThunderstoreBrowserTab {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            state,
+            sort: Config::new().thunderstore_browser_sort,
+            search: String::new(),
+            list: None,
+            conflict_confirmation: None,
+            dependency_window: None,
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) -> TabResult {
+        let snapshot = self.state.snapshot();
+        if let Some(cmd) = self.show_conflict_confirmation(ui) {
+            return Ok(Some(cmd));
+        }
+        self.show_controls(ui)?;
+        if self
+            .list
+            .as_ref()
+            .is_none_or(|x| x.is_stale(&snapshot, &self.sort, &self.search))
+        {
+            self.list = Some(BrowserList::new(&snapshot, &self.sort, &self.search));
+        }
+        let Some(list) = &self.list else {
+            return Ok(None);
+        };
+        let mut show_dependencies = None;
+        let command = draw_thunderstore_browser(ui, &list.mods, self.state.icons(), &mut show_dependencies);
+        if let Some(library) = show_dependencies {
+            self.dependency_window = Some(DependencyWindow::new(&self.state, library));
+        }
+        if let Some(window) = &mut self.dependency_window
+            && !window.show(ui, &self.state)
+        {
+            self.dependency_window = None;
+        }
+        // adding a mod enables it, so check it against what's already enabled first
+        if let Some(AppCommand::CacheModByID(id, None)) = &command
+            && let Some(new_mod) = snapshot.index().mods.iter().find(|x| x.uuid == *id)
+        {
+            let conflicts = snapshot.find_conflicts(self.state.options(), &[new_mod]);
+            if !conflicts.is_empty() {
+                self.conflict_confirmation = Some((new_mod.clone(), conflicts));
+                return Ok(None);
             }
+        }
+        Ok(command)
+    }
+
+    /// Sort picker and search box
+    fn show_controls(&mut self, ui: &mut Ui) -> color_eyre::eyre::Result<()> {
+        let mut selected_sort = self.sort.clone();
+        ui.horizontal(|ui| {
+            ComboBox::from_id_salt("Browser Sort")
+                .selected_text(String::from(self.sort.clone()))
+                .show_ui(ui, |ui| {
+                    for option in [SortType::Alphabetically, SortType::UpdateDate, SortType::ReleaseDate] {
+                        let label = String::from(option.clone());
+                        ui.selectable_value(&mut selected_sort, option, label);
+                    }
+                });
+            ui.label("Search:");
+            ui.text_edit_singleline(&mut self.search);
         });
-    let selected_sort_type = SortType::from(selected_sort);
-    if sort != selected_sort_type {
-        config.thunderstore_browser_sort = selected_sort_type;
-        config.save_to_file()?;
+        if selected_sort != self.sort {
+            self.sort = selected_sort;
+            let mut config = Config::new();
+            config.thunderstore_browser_sort = self.sort.clone();
+            config.save_to_file()?;
+        }
+        Ok(())
     }
-    let mut out = Ok(None);
-    let command = egui::Grid::new("Mod Grid")
-        .striped(true)
-        .show(ui, |ui| -> Option<AppCommand> {
-            for new_mod in &sorted_mod_list.mods {
-                if new_mod.name == "GaleModManager" { // Ban Gale Mod Manager 
-                    continue;
-                }
-                // mod icon
-                ui.image(
-                    new_mod
-                        .versions
-                        .first()
-                        .expect("mods should always have a first version")
-                        .icon
-                        .clone(),
-                );
-                // mod name
-                ui.label(
-                    new_mod
-                        .versions
-                        .first()
-                        .expect("mods should always have a first version")
-                        .name
-                        .clone(),
-                );
-                if ui.add(egui::Button::new("Add Mod")).clicked() {
-                    out = Ok(Some(AppCommand::CacheModByID(new_mod.uuid, None)));
+
+    fn show_conflict_confirmation(&mut self, ui: &mut Ui) -> Option<AppCommand> {
+        let (new_mod, conflicts) = self.conflict_confirmation.as_ref()?;
+        let mut close = false;
+        let mut out = None;
+        egui::Window::new(format!("Add {}?", new_mod.name))
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ui.ctx(), |ui| {
+                for conflict in conflicts {
+                    ui.label(format!("⚠ {conflict}"));
                 }
-                ui.end_row();
-            }
-            None
-        })
-        .inner;
-    if let Some(cmd) = command {
-        return Ok(Some(cmd));
+                ui.horizontal(|ui| {
+                    if ui.button("Add Anyway").clicked() {
+                        out = Some(AppCommand::CacheModByID(new_mod.uuid, None));
+                        close = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+        if close {
+            self.conflict_confirmation = None;
+        }
+        out
     }
+}
+
+/// Renders the list of mods. Only the rows in view get laid out.
+/// `show_dependencies` gets the mod whose Dependencies button was clicked
+fn draw_thunderstore_browser(
+    ui: &mut Ui,
+    mods: &[Mod],
+    icons: &IconCache,
+    show_dependencies: &mut Option<Mod>,
+) -> Option<AppCommand> {
+    let mut out = None;
+    let row_height = THUMBNAIL_SIZE as f32 + ui.spacing().item_spacing.y;
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show_rows(ui, row_height, mods.len(), |ui, visible_rows| {
+            egui::Grid::new("Mod Grid")
+                .striped(true)
+                .min_row_height(THUMBNAIL_SIZE as f32)
+                .show(ui, |ui| {
+                    for new_mod in &mods[visible_rows] {
+                        // the list never has mods without versions
+                        let Some(latest) = new_mod.versions.first() else {
+                            continue;
+                        };
+                        ui.add(mod_icon(icons, latest));
+                        ui.label(&latest.name);
+                        if ui.add(egui::Button::new("Add Mod")).clicked() {
+                            out = Some(AppCommand::CacheModByID(new_mod.uuid, None));
+                        }
+                        if ui.button("Dependencies").clicked() {
+                            *show_dependencies = Some(new_mod.clone());
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
     out
 }
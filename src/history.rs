@@ -0,0 +1,186 @@
+// Per-mod history of version changes, so an update can be rolled back.
+// Stored at `[Cache Dir]/[Mod ID]/history.json`, oldest entry first
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use color_eyre::eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::config_and_such::Config;
+use crate::mod_cache::ModCache;
+use crate::version_requirement::VersionRequirement;
+
+const HISTORY_FILE_NAME: &str = "history.json";
+
+/// What caused a version change
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum UpdateTrigger {
+    /// Updating a single mod, or picking a version by hand
+    Manual,
+    UpdateAll,
+    /// The background updater (`rumm --updater`)
+    Daemon,
+    Revert,
+}
+
+impl std::fmt::Display for UpdateTrigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateTrigger::Manual => write!(f, "manual"),
+            UpdateTrigger::UpdateAll => write!(f, "update all"),
+            UpdateTrigger::Daemon => write!(f, "background updater"),
+            UpdateTrigger::Revert => write!(f, "revert"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    pub from: String,
+    pub to: String,
+    pub trigger: UpdateTrigger,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModHistory {
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl ModHistory {
+    fn path(config: &Config, mod_id: &Uuid) -> PathBuf {
+        config
+            .mod_cache_directory
+            .join(mod_id.to_string())
+            .join(HISTORY_FILE_NAME)
+    }
+
+    /// Empty if the mod has never changed version
+    pub fn load(config: &Config, mod_id: &Uuid) -> Self {
+        fs::read_to_string(Self::path(config, mod_id))
+            .ok()
+            .and_then(|x| serde_json::from_str(&x).ok())
+            .unwrap_or_default()
+    }
+
+    /// Adds an entry to the mod's history. Does nothing if the version didn't actually change
+    pub fn record(config: &Config, mod_id: &Uuid, from: &str, to: &str, trigger: UpdateTrigger) -> Result<()> {
+        if from == to {
+            return Ok(());
+        }
+        let mut history = Self::load(config, mod_id);
+        history.entries.push(HistoryEntry {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            from: from.to_string(),
+            to: to.to_string(),
+            trigger,
+        });
+        let path = Self::path(config, mod_id);
+        fs::create_dir_all(path.parent().ok_or(eyre!("history path has no parent"))?)?;
+        fs::write(path, serde_json::to_string_pretty(&history)?)?;
+        Ok(())
+    }
+
+    /// The version reverting goes back to. Each revert undoes the change before it, so reverting again
+    /// keeps walking back through the history instead of bouncing between two versions
+    pub fn get_previous_version(&self) -> Option<&str> {
+        let mut reverts = 0;
+        for entry in self.entries.iter().rev() {
+            if entry.trigger == UpdateTrigger::Revert {
+                reverts += 1;
+            } else if reverts > 0 {
+                reverts -= 1;
+            } else {
+                return Some(&entry.from);
+            }
+        }
+        None
+    }
+}
+
+/// Formats a timestamp relative to now, like `3 days ago`
+pub fn format_time_ago(timestamp: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(timestamp);
+    let secs = now.saturating_sub(timestamp);
+    let (amount, unit) = match secs {
+        0..60 => return "just now".to_string(),
+        60..3600 => (secs / 60, "minute"),
+        3600..86400 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+    if amount == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{amount} {unit}s ago")
+    }
+}
+
+impl ModCache {
+    /// Puts a mod back on the version it had before its latest change, downloading it again if it
+    /// was removed from the cache. The version gets locked so the next update doesn't undo the revert.
+    /// Returns the version it reverted to
+    pub async fn revert_mod(&mut self, config: &Config, mod_id: &Uuid) -> Result<String> {
+        let history = ModHistory::load(config, mod_id);
+        let previous = history
+            .get_previous_version()
+            .ok_or(eyre!("This mod has no history to revert to"))?
+            .to_string();
+        let current = self
+            .options()
+            .read(|options| options.get_mod_options(mod_id.to_string()).map(|x| x.version.clone()))
+            .ok_or(eyre!("Could not find options for mod: {}", mod_id))?;
+        // pinned, otherwise the next update would undo the revert
+        let pin = VersionRequirement::exact(&previous)?;
+
+        let reverted_mod = self
+            .cache_mod_by_mod_id(&mod_id.to_string(), Some(&previous))
+            .await?;
+        self.options().update(|options| {
+            options.set_mod_version(mod_id, &previous);
+            options.set_version_requirement(mod_id, pin);
+        })?;
+        ModHistory::record(config, mod_id, &current, &previous, UpdateTrigger::Revert)?;
+        self.sync_mod_to_rumble(&reverted_mod, config).await?;
+        Ok(previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(changes: &[(&str, &str, UpdateTrigger)]) -> ModHistory {
+        ModHistory {
+            entries: changes
+                .iter()
+                .map(|(from, to, trigger)| HistoryEntry {
+                    timestamp: 0,
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    trigger: *trigger,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn reverting_walks_back_through_history() {
+        use UpdateTrigger::*;
+        let mut changes = vec![("1.0.0", "2.0.0", UpdateAll), ("2.0.0", "3.0.0", Daemon)];
+        assert_eq!(history(&changes).get_previous_version(), Some("2.0.0"));
+        changes.push(("3.0.0", "2.0.0", Revert));
+        assert_eq!(history(&changes).get_previous_version(), Some("1.0.0"));
+        changes.push(("2.0.0", "1.0.0", Revert));
+        assert_eq!(history(&changes).get_previous_version(), None);
+        // a new change after reverting can be reverted on its own
+        changes.push(("1.0.0", "4.0.0", Manual));
+        assert_eq!(history(&changes).get_previous_version(), Some("1.0.0"));
+    }
+}
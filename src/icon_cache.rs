@@ -0,0 +1,154 @@
+// Thumbnails of mod icons, so the browser and Mods tab don't fetch every icon over the network and still show them offline.
+// Each one is `[Cache Dir]/.icons/[Version ID].png`, scaled down to `THUMBNAIL_SIZE`.
+// Icons from extracted archives are used when there is one, otherwise it's downloaded from Thunderstore
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use color_eyre::eyre::Result;
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
+
+use crate::config_and_such::Config;
+use crate::thunderstore::{Mod, Version};
+
+/// Thumbnails, inside the mod cache
+const ICON_DIR_NAME: &str = ".icons";
+/// Width and height of a thumbnail, in pixels
+pub const THUMBNAIL_SIZE: u32 = 64;
+/// How many icons get downloaded at once while filling the cache
+const DOWNLOADS_AT_ONCE: usize = 8;
+
+/// Cheap to clone, every clone shares the same list of cached icons
+#[derive(Clone)]
+pub struct IconCache {
+    /// Absolute, since the gui loads icons by `file://` URI
+    dir: PathBuf,
+    cache_directory: PathBuf,
+    /// Version IDs that have a thumbnail, so drawing a frame doesn't touch the disk
+    cached: Arc<RwLock<HashSet<String>>>,
+}
+
+impl IconCache {
+    pub fn new(config: &Config) -> Self {
+        let dir = config.mod_cache_directory.join(ICON_DIR_NAME);
+        let dir = std::path::absolute(&dir).unwrap_or(dir);
+        let cached = fs::read_dir(&dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|x| x.ok())
+                    .filter_map(|x| x.file_name().to_str()?.strip_suffix(".png").map(|x| x.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            dir,
+            cache_directory: config.mod_cache_directory.clone(),
+            cached: Arc::new(RwLock::new(cached)),
+        }
+    }
+
+    /// The thumbnail for this version, if it's been cached
+    pub fn get(&self, version: &Version) -> Option<PathBuf> {
+        self.cached
+            .read()
+            .unwrap()
+            .contains(&version.uuid4)
+            .then(|| self.path(&version.uuid4))
+    }
+
+    fn path(&self, version_id: &str) -> PathBuf {
+        self.dir.join(format!("{version_id}.png"))
+    }
+
+    /// Scales the image down and saves it as the thumbnail for the version
+    fn store(&self, version_id: &str, bytes: &[u8]) -> Result<()> {
+        let thumbnail = image::load_from_memory(bytes)?.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+        fs::create_dir_all(&self.dir)?;
+        // written to the side first, so the gui never loads half a file
+        let temp_path = self.dir.join(format!("{version_id}.png.tmp"));
+        thumbnail.save_with_format(&temp_path, image::ImageFormat::Png)?;
+        fs::rename(temp_path, self.path(version_id))?;
+        self.cached.write().unwrap().insert(version_id.to_string());
+        Ok(())
+    }
+
+    /// The `icon.png` from an extracted archive of this mod. Prefers the version itself, but any version's will do
+    fn find_extracted_icon(&self, m: &Mod, version: &Version) -> Option<PathBuf> {
+        let versions_dir = self.cache_directory.join(m.uuid.to_string()).join("versions");
+        let own_icon = versions_dir.join(&version.version_number).join("icon.png");
+        if own_icon.exists() {
+            return Some(own_icon);
+        }
+        fs::read_dir(versions_dir)
+            .ok()?
+            .filter_map(|x| Some(x.ok()?.path().join("icon.png")))
+            .find(|x| x.exists())
+    }
+
+    /// Caches the icon of each mod's newest version, calling `on_progress` every time a batch lands.
+    /// Failures are only logged, the mod just keeps its placeholder
+    pub async fn fill(&self, mods: Vec<Mod>, on_progress: impl Fn()) {
+        let mut missing: Vec<(Mod, Version)> = vec![];
+        for m in mods {
+            let Some(version) = m.versions.first().cloned() else {
+                continue;
+            };
+            if self.get(&version).is_none() && !missing.iter().any(|(_, x)| x.uuid4 == version.uuid4) {
+                missing.push((m, version));
+            }
+        }
+        if missing.is_empty() {
+            return;
+        }
+        debug!("caching {} icons", missing.len());
+        for batch in missing.chunks(DOWNLOADS_AT_ONCE) {
+            let mut downloads = JoinSet::new();
+            for (m, version) in batch {
+                let icons = self.clone();
+                let local_icon = self.find_extracted_icon(m, version);
+                let (name, version) = (m.name.clone(), version.clone());
+                downloads.spawn(async move {
+                    let bytes = match local_icon {
+                        Some(path) => fs::read(path).map_err(Into::into),
+                        None => download(&version.icon).await,
+                    };
+                    let result = bytes.and_then(|bytes| icons.store(&version.uuid4, &bytes));
+                    if let Err(e) = result {
+                        warn!("could not cache the icon for {name}: {e}");
+                    }
+                });
+            }
+            downloads.join_all().await;
+            on_progress();
+        }
+    }
+}
+
+async fn download(url: &str) -> Result<Vec<u8>> {
+    Ok(reqwest::get(url).await?.error_for_status()?.bytes().await?.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn icons_are_scaled_down() -> Result<()> {
+        let dir = TestDir::new("icon");
+        let config = dir.config();
+        let icons = IconCache::new(&config);
+        let mut png = std::io::Cursor::new(vec![]);
+        image::RgbaImage::new(256, 256).write_to(&mut png, image::ImageFormat::Png)?;
+        icons.store("some-version", png.get_ref())?;
+
+        let thumbnail = image::open(icons.path("some-version"))?;
+        assert_eq!((thumbnail.width(), thumbnail.height()), (THUMBNAIL_SIZE, THUMBNAIL_SIZE));
+        // a fresh cache finds it on disk
+        assert!(IconCache::new(&config).cached.read().unwrap().contains("some-version"));
+        Ok(())
+    }
+}
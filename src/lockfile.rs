@@ -0,0 +1,222 @@
+// Lockfiles pin an exact set of mods, so everyone on a team can run byte-identical files.
+// `rumm lock` writes one from the enabled mods, `rumm install --locked` reproduces it
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{Context, Result, eyre};
+use serde::{Deserialize, Serialize};
+
+use crate::blob_store::VersionManifest;
+use crate::config_and_such::Config;
+use crate::mod_cache::{ModCache, SyncSummary};
+use crate::thunderstore::{Mod, Version};
+use crate::version_requirement::VersionRequirement;
+
+pub const LOCKFILE_NAME: &str = "rumm.lock";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub mods: Vec<LockedMod>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedMod {
+    /// Thunderstore's `Owner-Name`
+    pub full_name: String,
+    pub version: String,
+    pub download_url: String,
+    /// sha256 of the zip downloaded from Thunderstore
+    pub archive_hash: String,
+}
+
+impl Lockfile {
+    /// `config/rumm.lock`, next to the mod options
+    pub fn default_path(config: &Config) -> PathBuf {
+        config.config_file.with_file_name(LOCKFILE_NAME)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Could not read lockfile {:?}", path))?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl ModCache {
+    /// Writes a lockfile with the selected version of every enabled mod
+    pub async fn write_lockfile(&self, config: &Config, path: &Path) -> Result<Lockfile> {
+        let options = self.options().snapshot();
+        let mut lockfile = Lockfile::default();
+        for cached_mod in &self.cache_mod_list {
+            let Some(mod_options) = options.get_mod_options(cached_mod.uuid.to_string()) else {
+                continue;
+            };
+            if !mod_options.enabled {
+                continue;
+            }
+            let version = cached_mod
+                .versions
+                .iter()
+                .find(|x| x.version_number == mod_options.version)
+                .ok_or(eyre!(
+                    "Version {} of {} could not be found",
+                    mod_options.version,
+                    cached_mod.full_name
+                ))?;
+            lockfile.mods.push(LockedMod {
+                full_name: cached_mod.full_name.clone(),
+                version: version.version_number.clone(),
+                download_url: version.download_url.clone(),
+                archive_hash: self.get_archive_hash(config, cached_mod, version).await?,
+            });
+        }
+        lockfile.mods.sort_by(|a, b| a.full_name.cmp(&b.full_name));
+        lockfile.save(path)?;
+        Ok(lockfile)
+    }
+
+    /// Makes the enabled mods exactly match the lockfile, then syncs to Rumble.
+    /// Fails if a locked version is gone from Thunderstore or if any archive hash differs
+    pub async fn install_locked(&mut self, config: &Config, path: &Path) -> Result<SyncSummary> {
+        let lockfile = Lockfile::load(path)?;
+        let mut locked_mods = vec![];
+        for locked in &lockfile.mods {
+            let pin = VersionRequirement::exact(&locked.version)?;
+            let this_mod = self
+                .get_thunderstore_mod_list()
+                .mods
+                .iter()
+                .find(|x| x.full_name == locked.full_name)
+                .cloned()
+                .ok_or(eyre!(
+                    "{} is in the lockfile but is no longer on Thunderstore",
+                    locked.full_name
+                ))?;
+            let version = this_mod
+                .versions
+                .iter()
+                .find(|x| x.version_number == locked.version)
+                .cloned()
+                .ok_or(eyre!(
+                    "Version {} of {} has disappeared from Thunderstore",
+                    locked.version,
+                    locked.full_name
+                ))?;
+            match ModCache::intact_archive_hash(config, &this_mod.uuid, &locked.version) {
+                Some(cached_hash) if cached_hash != locked.archive_hash => {
+                    return Err(eyre!(
+                        "Cached {} {} does not match the lockfile (expected hash {}, got {})",
+                        locked.full_name,
+                        locked.version,
+                        locked.archive_hash,
+                        cached_hash
+                    ));
+                }
+                Some(_) => {}
+                // damaged or never hashed, so the cached files can't be trusted to be what was locked
+                None if ModCache::is_version_cached(config, &this_mod.uuid, &locked.version) => {
+                    self.redownload_version(config, &this_mod.uuid, &version, Some(&locked.archive_hash))
+                        .await?;
+                }
+                None => {
+                    self.download_version_to_cache(config, &this_mod, &version, Some(&locked.archive_hash))
+                        .await?;
+                }
+            }
+            locked_mods.push((this_mod, pin));
+        }
+
+        self.options().update(|options| {
+            for enabled_id in options.get_enabled_mod_ids() {
+                if !locked_mods.iter().any(|(x, _)| x.uuid == enabled_id)
+                    && let Some(mod_options) = options.get_mod_options_mut(enabled_id.to_string())
+                {
+                    mod_options.enabled = false;
+                }
+            }
+            for ((locked_mod, pin), locked) in locked_mods.into_iter().zip(&lockfile.mods) {
+                options.enable_mod(&locked_mod);
+                options.set_mod_version(&locked_mod.uuid, &locked.version);
+                options.set_version_requirement(&locked_mod.uuid, pin);
+            }
+        })?;
+        self.update_self_from_cache()?;
+        self.sync_all_mods_to_rumble(config).await
+    }
+
+    /// Gets the hash of a cached version's original zip. Versions that were never hashed or no longer match
+    /// their manifest get downloaded again, so the hash always describes the files that are actually cached
+    async fn get_archive_hash(&self, config: &Config, this_mod: &Mod, version: &Version) -> Result<String> {
+        if let Some(hash) = ModCache::intact_archive_hash(config, &this_mod.uuid, &version.version_number) {
+            return Ok(hash);
+        }
+        // a damaged copy has to come back as the same archive, one that was never hashed can be anything
+        let expected_hash = VersionManifest::load(config, &this_mod.uuid, &version.version_number)
+            .and_then(|x| x.archive_hash);
+        let manifest = self
+            .redownload_version(config, &this_mod.uuid, version, expected_hash.as_deref())
+            .await?;
+        manifest.archive_hash.ok_or(eyre!("{} {} has no archive hash", this_mod.full_name, version.version_number))
+    }
+}
+
+/// Versions in the default lockfile, as `(full name, version)`. Empty if there is no lockfile
+pub fn get_locked_versions(config: &Config) -> Vec<(String, String)> {
+    Lockfile::load(&Lockfile::default_path(config))
+        .map(|x| x.mods.into_iter().map(|x| (x.full_name, x.version)).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_and_such::ModOptionsStore;
+    use crate::test_dir::TestDir;
+
+    #[tokio::test]
+    async fn tampered_cache_is_refused() -> Result<()> {
+        let dir = TestDir::new("lockfile");
+        let config = dir.config();
+        let m = Mod::for_test("Owner-Thing", &[("1.0.0", &[])]);
+        let version_dir = config.mod_cache_directory.join(m.uuid.to_string()).join("versions").join("1.0.0");
+        fs::create_dir_all(version_dir.join("Mods"))?;
+        fs::write(version_dir.join("Mods").join("Thing.dll"), b"not what was locked")?;
+        ModCache::store_version_files(&config, &m.uuid, "1.0.0", Some("cached hash".to_string()))?;
+        let mut cache = ModCache::for_test(vec![m.clone()], ModOptionsStore::new(&config)?);
+        let path = Lockfile::default_path(&config);
+        Lockfile {
+            mods: vec![LockedMod {
+                full_name: m.full_name.clone(),
+                version: "1.0.0".to_string(),
+                download_url: String::new(),
+                archive_hash: "locked hash".to_string(),
+            }],
+        }
+        .save(&path)?;
+
+        let error = cache.install_locked(&config, &path).await.unwrap_err();
+        assert!(error.to_string().contains("does not match the lockfile"), "{error}");
+        // nothing got enabled
+        assert!(cache.options().read(|options| options.get_enabled_mod_ids()).is_empty());
+
+        // the recorded hash matches now, but the dll itself was changed after it got cached
+        let mut manifest = VersionManifest::load(&config, &m.uuid, "1.0.0").unwrap();
+        manifest.archive_hash = Some("locked hash".to_string());
+        manifest.save(&config, &m.uuid, "1.0.0")?;
+        fs::write(version_dir.join("Mods").join("Thing.dll"), b"tampered")?;
+        assert!(ModCache::intact_archive_hash(&config, &m.uuid, "1.0.0").is_none());
+        // it has to be downloaded again, and there's nowhere to download it from
+        assert!(cache.install_locked(&config, &path).await.is_err());
+        assert!(cache.options().read(|options| options.get_enabled_mod_ids()).is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+// Logging setup. Everything goes to the terminal, to a daily log file in `config/logs` (the last
+// `MAX_LOG_FILES` days are kept), and to an in-memory buffer that the Logs tab reads from.
+// Operations that deal with one mod run in a span with a `mod_name` field, so logs can be filtered by mod
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs;
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use color_eyre::eyre::Result;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{Builder, Rotation};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+pub const LOG_DIRECTORY: &str = "config/logs";
+const MAX_LOG_FILES: usize = 7;
+/// How many entries the Logs tab can show. Older ones are still in the log file
+const MAX_BUFFERED_ENTRIES: usize = 5000;
+
+static LOG_BUFFER: OnceLock<Mutex<VecDeque<LogEntry>>> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: SystemTime,
+    pub level: Level,
+    pub message: String,
+    /// The mod this happened to, if it happened inside a span with a `mod_name` field
+    pub mod_name: Option<String>,
+}
+
+/// Starts logging. The returned guard flushes the log file when dropped, so keep it around until exit
+pub fn init() -> Result<WorkerGuard> {
+    // the appender prunes old files on startup and complains if the folder isn't there
+    fs::create_dir_all(LOG_DIRECTORY)?;
+    let file_appender = Builder::new()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("rumm")
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(LOG_DIRECTORY)?;
+    let (file_writer, guard) = tracing_appender::non_blocking(file_appender);
+    // our own logs at debug, dependencies only when something goes wrong
+    let filter = Targets::new()
+        .with_target("rumm", Level::DEBUG)
+        .with_default(Level::WARN);
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_target(false))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(file_writer),
+        )
+        .with(BufferLayer)
+        .with(filter)
+        .try_init()?;
+    Ok(guard)
+}
+
+/// A copy of the buffered log entries, oldest first
+pub fn get_recent_logs() -> Vec<LogEntry> {
+    LOG_BUFFER
+        .get()
+        .map(|x| x.lock().unwrap().iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Feeds events into `LOG_BUFFER` for the Logs tab
+struct BufferLayer;
+
+/// Stored on spans that have a `mod_name` field
+struct ModName(String);
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for BufferLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        attrs.record(&mut visitor);
+        if let Some(mod_name) = visitor.mod_name
+            && let Some(span) = ctx.span(id)
+        {
+            span.extensions_mut().insert(ModName(mod_name));
+        }
+    }
+
+    // for spans that only find out which mod they're about part way through
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        values.record(&mut visitor);
+        if let Some(mod_name) = visitor.mod_name
+            && let Some(span) = ctx.span(id)
+        {
+            span.extensions_mut().replace(ModName(mod_name));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        // the closest span that knows which mod this is about
+        let mod_name = visitor.mod_name.or_else(|| {
+            ctx.event_scope(event)?
+                .find_map(|span| span.extensions().get::<ModName>().map(|x| x.0.clone()))
+        });
+        let entry = LogEntry {
+            time: SystemTime::now(),
+            level: *event.metadata().level(),
+            message: visitor.message,
+            mod_name,
+        };
+        let mut buffer = LOG_BUFFER.get_or_init(Default::default).lock().unwrap();
+        if buffer.len() >= MAX_BUFFERED_ENTRIES {
+            buffer.pop_front();
+        }
+        buffer.push_back(entry);
+    }
+}
+
+/// Builds a single line out of an event's message and fields, pulling out `mod_name`
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    mod_name: Option<String>,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "mod_name" {
+            self.mod_name = Some(value.to_string());
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => {
+                let _ = write!(self.message, "{value:?}");
+            }
+            "mod_name" => self.mod_name = Some(format!("{value:?}")),
+            name => {
+                let _ = write!(self.message, " {name}={value:?}");
+            }
+        }
+    }
+}
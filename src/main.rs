@@ -1,26 +1,108 @@
 use color_eyre::eyre::{Result, eyre};
 use mod_cache::ModCache;
 use std::{env, path::{Path, PathBuf}, str::FromStr, time::Duration};
-use config_and_such::Config;
+use config_and_such::{Config, ModOptionsStore};
+use tracing::{error, info, warn};
 
+mod blob_store;
+mod bundle;
+mod cache_usage;
+mod dependencies;
+mod drift;
 mod egui;
+mod history;
+mod icon_cache;
+mod logging;
+mod lockfile;
 mod mod_cache;
+mod rules;
 mod thunderstore;
+mod trash;
 mod updater;
+mod verify;
+mod version_requirement;
 mod config_and_such;
+#[cfg(test)]
+mod test_dir;
 
 use egui::start_gui;
+use history::UpdateTrigger;
+use lockfile::Lockfile;
 use thunderstore::ModList;
 
 fn main() -> Result<()> {
     color_eyre::install()?;
+    let _log_guard = logging::init()?;
+    let args: Vec<String> = env::args().collect();
+    // offline bundles can be made/used without opening the gui, like `rumm --import-bundle bundle.zip`
+    if let Some(path) = get_arg_value(&args, "--export-bundle") {
+        let config = Config::new();
+        let cache = load_mod_cache(&config)?;
+        let all_versions = args.iter().any(|x| x == "--all-versions");
+        let summary = bundle::export_bundle(&config, &cache, all_versions, Path::new(path))?;
+        info!("Exported {summary} to {path}");
+        return Ok(());
+    }
+    if let Some(path) = get_arg_value(&args, "--import-bundle") {
+        let config = Config::new();
+        let mut cache = load_mod_cache(&config)?;
+        let summary = bundle::import_bundle(&config, &mut cache, Path::new(path))?;
+        info!("Imported {summary} from {path}");
+        return Ok(());
+    }
+    // lockfiles: `rumm lock` writes one, `rumm install --locked` reproduces it
+    if let Some(subcommand @ ("lock" | "install")) = args.get(1).map(|x| x.as_str()) {
+        let config = Config::new();
+        let path = get_arg_value(&args, "--lockfile")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| Lockfile::default_path(&config));
+        let mut cache = load_mod_cache(&config)?;
+        let runtime = tokio::runtime::Runtime::new()?;
+        if subcommand == "lock" {
+            let lockfile = runtime.block_on(cache.write_lockfile(&config, &path))?;
+            info!("Locked {} mods in {:?}", lockfile.mods.len(), path);
+        } else if args.iter().any(|x| x == "--locked") {
+            let summary = runtime.block_on(cache.install_locked(&config, &path))?;
+            info!("Installed the mods from {:?}, synced {summary}", path);
+        } else {
+            return Err(eyre!("`install` only supports `--locked` for now"));
+        }
+        return Ok(());
+    }
+    // `rumm drift` lists what changed in the Rumble folder since the last sync
+    if args.get(1).is_some_and(|x| x == "drift") {
+        let config = Config::new();
+        let report = load_mod_cache(&config)?.check_drift(&config)?;
+        if report.is_empty() {
+            info!("The Rumble folder matches your enabled mods");
+        } else {
+            warn!("The Rumble folder doesn't match your enabled mods: {report}");
+            for file in &report.missing {
+                warn!("missing: {file}");
+            }
+            for file in &report.modified {
+                warn!("modified: {file}");
+            }
+            for file in &report.unknown {
+                warn!("unknown: {file}");
+            }
+        }
+        return Ok(());
+    }
+    // `rumm verify` checks and repairs every enabled mod, like `Verify Installation` in the Mods tab
+    if args.get(1).is_some_and(|x| x == "verify") {
+        let config = Config::new();
+        let cache = load_mod_cache(&config)?;
+        let summary = tokio::runtime::Runtime::new()?.block_on(cache.verify_installation(&config))?;
+        info!("Verified {summary}");
+        return Ok(());
+    }
     // putting this here is janky, should rework in the future
     let path = PathBuf::from_str("config/thunderstore-mods.json")?;
     spawn_cached_thunderstore_response_updater(path.clone());
     
     if env::args().any(|x| x == "--updater") {
-        //update_loop().await
-        todo!()
+        tokio::runtime::Runtime::new()?.block_on(update_loop())
     } else {
         match start_gui() {
             Ok(_) => Ok(()),
@@ -29,20 +111,38 @@ fn main() -> Result<()> {
     }
 }
 
-// Disabled for now, eventually will make it so that it can auto-update mods in the background
-/* async fn update_loop() -> Result<()> {
+/// Auto-updates mods in the background, run with `rumm --updater`
+async fn update_loop() -> Result<()> {
+    let sleep_minutes = 30;
     loop {
         //update
         let config = Config::new();
-        let mod_list = &ModList::new()?;
-        let mut cache = ModCache::new(mod_list);
-        cache.update_all_mods(&config).await?;
+        let result = match load_mod_cache(&config) {
+            Ok(mut cache) => cache.update_all_mods(&config, UpdateTrigger::Daemon).await,
+            Err(e) => Err(e),
+        };
+        // something like being offline once shouldn't stop the updater for good
+        if let Err(e) = result {
+            error!("background update failed, trying again in {sleep_minutes} minutes: {e:?}");
+        }
 
         // sleep
-        let sleep_minutes = 30;
         tokio::time::sleep(Duration::from_secs(sleep_minutes * 60)).await;
     }
-} */
+}
+
+/// Loads the cache and options the same way the gui does, for the command line modes
+fn load_mod_cache(config: &Config) -> Result<ModCache> {
+    Ok(ModCache::new(
+        &ModList::new(PathBuf::from(thunderstore::MOD_LIST_CACHE_PATH))?,
+        ModOptionsStore::new(config)?,
+    ))
+}
+
+/// Gets the value after a flag, like `bundle.zip` in `--import-bundle bundle.zip`
+fn get_arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a String> {
+    args.iter().position(|x| x == flag).and_then(|i| args.get(i + 1))
+}
 
 /// Automatically stores a response from thunderstore to make startups faster (plus can still see mods even when offline)
 fn spawn_cached_thunderstore_response_updater(cache_path: PathBuf) {
@@ -52,10 +152,10 @@ fn spawn_cached_thunderstore_response_updater(cache_path: PathBuf) {
                 Ok(list) => {
                     if let Err(e) = std::fs::write(&cache_path, serde_json::to_vec(&list).unwrap())
                     {
-                        eprintln!("cannot write cache: {e}");
+                        error!("cannot write cache: {e}");
                     }
                 }
-                Err(e) => eprintln!("refresh failed: {e}"),
+                Err(e) => warn!("refresh failed: {e}"),
             }
             std::thread::sleep(std::time::Duration::from_secs(60 * 60)); // every hour
         }
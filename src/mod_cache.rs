@@ -1,47 +1,159 @@
 use std::ffi::OsString;
 use std::fs;
-use std::fs::read_dir;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use async_recursion::async_recursion;
 use color_eyre::eyre::Context;
 use color_eyre::eyre::Result;
 use color_eyre::eyre::eyre;
 use rust_search::SearchBuilder;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument, warn};
 use uuid::Uuid;
 
+use crate::blob_store::BlobStore;
+use crate::blob_store::DeploymentRecord;
+use crate::blob_store::VersionManifest;
+use crate::history::ModHistory;
+use crate::history::UpdateTrigger;
+use crate::rules::Rules;
 use crate::thunderstore::Mod;
 use crate::thunderstore::ModList;
 use crate::thunderstore::Version;
+use crate::trash::Trash;
 use crate::config_and_such::Config;
-use crate::config_and_such::LocalModOptions;
+use crate::config_and_such::ModOptionsStore;
+use crate::config_and_such::UpdatePolicy;
 
 /// Stores local copies of mods, and handles putting mods into and out of the rumble directory.
-/// Each Entry is stored as `[Cache Directory]/[Mod ID]/{mod_info.json, versions/{latest, 1.0.0}}`
-/// Then within each version the actual files are placed
+/// Each Entry is stored as `[Cache Directory]/[Mod ID]/{mod_info.json, versions/{latest, 1.0.0}, manifests/{1.0.0.json}}`
+/// Then within each version the actual files are placed, as links into the blob store (see `blob_store.rs`)
 #[derive(Debug, Clone)]
 pub struct ModCache {
-    /// Full mod list from thunderstore
-    thunderstore_mod_list: ModList,
+    /// Full mod list from thunderstore. Shared, so cloning the cache doesn't copy every mod on Thunderstore
+    thunderstore_mod_list: Arc<ModList>,
     /// List of mods in the cache
     pub cache_mod_list: Vec<Mod>,
+    /// Shared options for the mods (enabled, version, etc.)
+    options: ModOptionsStore,
+    /// Cancels whatever the cache is doing right now. Long operations check it between steps
+    cancel: CancellationToken,
+    /// Reloaded from the rule file every time the cache rescans
+    rules: Rules,
+    /// Set while the rule file doesn't parse. Installs and syncs are refused until it's fixed
+    rules_error: Option<String>,
 }
+
+/// What a sync to Rumble did
+#[derive(Debug, Clone, Default)]
+pub struct SyncSummary {
+    pub enabled_mods: usize,
+    /// Disabled mods get their files removed from Rumble
+    pub disabled_mods: usize,
+    pub files_copied: usize,
+    /// Files that were already deployed, so they were skipped
+    pub files_unchanged: usize,
+}
+
+impl SyncSummary {
+    pub fn add(&mut self, other: SyncSummary) {
+        self.enabled_mods += other.enabled_mods;
+        self.disabled_mods += other.disabled_mods;
+        self.files_copied += other.files_copied;
+        self.files_unchanged += other.files_unchanged;
+    }
+}
+
+impl std::fmt::Display for SyncSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} mods ({} files copied, {} unchanged), {} disabled",
+            self.enabled_mods, self.files_copied, self.files_unchanged, self.disabled_mods
+        )
+    }
+}
+
+/// Problems with an installed mod's package, see `ModCache::package_status`
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackageStatus {
+    /// Gone from Thunderstore, only the cached `mod_info.json` is left
+    Delisted,
+    /// ID and name of the mod the description points to instead, if it names one
+    Deprecated { replacement: Option<(Uuid, String)> },
+    /// The selected version was pulled by its author
+    InactiveVersion(String),
+}
+
+impl std::fmt::Display for PackageStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackageStatus::Delisted => {
+                write!(f, "No longer on Thunderstore. It still works from the cache, but won't get updates")
+            }
+            PackageStatus::Deprecated { replacement: Some((_, name)) } => {
+                write!(f, "Deprecated by its author, try {name} instead")
+            }
+            PackageStatus::Deprecated { replacement: None } => write!(f, "Deprecated by its author"),
+            PackageStatus::InactiveVersion(version) => {
+                write!(f, "Version {version} was pulled from Thunderstore, consider switching to another one")
+            }
+        }
+    }
+}
+
+/// A newer version a `NotifyOnly` mod could move to, see `ModCache::available_updates`
+#[derive(Debug, Clone)]
+pub struct AvailableUpdate {
+    pub mod_to_update: Mod,
+    pub current: String,
+    pub newest: String,
+}
+
+/// Returned when an operation gets cancelled through `ModCache::set_cancel_token`.
+/// Check for it with `report.is::<Cancelled>()`
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
 impl ModCache {
-    pub fn new(mod_list: &ModList) -> Self {
+    pub fn new(mod_list: &ModList, options: ModOptionsStore) -> Self {
         let mut cache = ModCache {
-            thunderstore_mod_list: mod_list.clone(),
+            thunderstore_mod_list: Arc::new(mod_list.clone()),
             cache_mod_list: vec![],
+            options,
+            cancel: CancellationToken::new(),
+            rules: Rules::default(),
+            rules_error: None,
         };
         if let Err(e) = cache.update_self_from_cache() {
-            eprintln!(
-                "mod‑cache incomplete: {}",
-                color_eyre::eyre::Report::from(e)
-            );
+            warn!("mod‑cache incomplete: {}", e);
         }
         cache
     }
+    /// A cache that already holds `mods`, without scanning a directory. They count as on Thunderstore too
+    #[cfg(test)]
+    pub fn for_test(mods: Vec<Mod>, options: ModOptionsStore) -> Self {
+        ModCache {
+            thunderstore_mod_list: Arc::new(ModList { mods: mods.clone() }),
+            cache_mod_list: mods,
+            options,
+            cancel: CancellationToken::new(),
+            rules: Rules::default(),
+            rules_error: None,
+        }
+    }
     /// Adds a mod into the cache using a mod's ID. Will download from Thunderstore
+    #[instrument(skip(self), fields(mod_name = tracing::field::Empty))]
     #[async_recursion]
     pub async fn cache_mod_by_mod_id(
         &mut self,
@@ -50,62 +162,145 @@ impl ModCache {
     ) -> Result<Mod> {
         let real_version = self.resolve_mod_version(id, version_name)?;
         let config = Config::new();
-        let _mod_options = LocalModOptions::new(&config);
-        let download_path = &config.mod_cache_directory;
         let this_mod = self
-            .thunderstore_mod_list
-            .mods
-            .iter()
-            .find(|x| x.uuid.to_string() == *id)
-            .ok_or_else(|| eyre!("could not convert UUID to string"))?
+            .find_mod(&Uuid::parse_str(id)?)
+            .ok_or_else(|| eyre!("Mod {id} is not on Thunderstore or in the cache"))?
             .clone();
+        tracing::Span::current().record("mod_name", &this_mod.name);
+        self.check_rules_loaded()?;
+        self.rules.check_allowed(&this_mod)?;
         // check if mod is already cached
         if self.is_mod_in_cache(&this_mod.uuid, Some(&real_version)) {
-            println!("{} already added to cache, skipping!", this_mod.name);
+            debug!("{} already added to cache, skipping!", this_mod.name);
             return Ok(this_mod);
         }
-        println!("caching mod: {}", this_mod.name);
+        self.check_cancelled()?;
+        info!("caching mod: {}", this_mod.name);
+        let was_cached = self.cache_mod_list.iter().any(|x| x.uuid == this_mod.uuid);
+        let cached_before: Vec<Uuid> = self.cache_mod_list.iter().map(|x| x.uuid).collect();
         let thunderstore_version = this_mod
             .versions
             .iter()
             .find(|x| x.version_number == real_version)
             .ok_or_else(|| eyre!("cache_mod_by_mod_id: could not find version"))?
             .clone(); //I LOVE CLONE(). I LOVE NOT THINKING ABOUT OWNERSHIP WOOOOO
+        self.download_version_to_cache(&config, &this_mod, &thunderstore_version, None)
+            .await?;
+
+        match self.cache_mod_dependancies(&this_mod, real_version.clone()).await {
+            // don't leave a mod behind without its dependencies, or dependencies nothing asked for
+            Err(e) if e.is::<Cancelled>() => {
+                self.update_self_from_cache()?;
+                let new_dependencies: Vec<Mod> = self
+                    .cache_mod_list
+                    .iter()
+                    .filter(|x| x.uuid != this_mod.uuid && !cached_before.contains(&x.uuid))
+                    .cloned()
+                    .collect();
+                for dependency in &new_dependencies {
+                    self.remove_mod_from_cache(&config, dependency)?;
+                }
+                self.options.update(|options| {
+                    for dependency in &new_dependencies {
+                        options.remove_mod_options(&dependency.uuid);
+                    }
+                })?;
+                if was_cached {
+                    self.remove_version_from_cache(&config, &this_mod, thunderstore_version)?;
+                } else {
+                    self.remove_mod_from_cache(&config, &this_mod)?;
+                    self.update_self_from_cache()?;
+                }
+                return Err(e);
+            }
+            result => {
+                // update config with the new version. Still done if a dependency failed, so the mod shows up
+                self.options.update(|options| options.enable_mod(&this_mod))?;
+                result?;
+            }
+        }
+
+        self.update_self_from_cache()?;
+        Ok(this_mod.clone())
+    }
+
+    /// Downloads one version of a mod, extracts it into the cache, and moves it into the blob store.
+    /// If `expected_hash` is given, the download is rejected (and nothing is written) unless the archive matches it
+    #[instrument(skip_all, fields(mod_name = %this_mod.name, version = %thunderstore_version.version_number))]
+    pub async fn download_version_to_cache(
+        &self,
+        config: &Config,
+        this_mod: &Mod,
+        thunderstore_version: &Version,
+        expected_hash: Option<&str>,
+    ) -> Result<()> {
         // Build the destination directory: <mod_cache_directory>/<mod id>/versions/<version id>
-        let mod_id_folder = id;
-        let version_id_folder = &thunderstore_version.version_number;
-        let destination_dir = download_path
-            .join(mod_id_folder)
+        let destination_dir = config
+            .mod_cache_directory
+            .join(this_mod.uuid.to_string())
             .join("versions")
-            .join(version_id_folder);
-        // Create the destination directory if it does not exist.
-        tokio::fs::create_dir_all(&destination_dir).await?;
+            .join(&thunderstore_version.version_number);
+        let archive_hash = self
+            .download_version_to(thunderstore_version, expected_hash, &destination_dir)
+            .await?;
+        ModCache::store_version_files(
+            config,
+            &this_mod.uuid,
+            &thunderstore_version.version_number,
+            Some(archive_hash),
+        )?;
+        ModCache::add_mod_config_json(this_mod, config)?;
+        ModCache::record_version_use(config, &this_mod.uuid, &thunderstore_version.version_number)?;
+        Ok(())
+    }
+
+    /// Downloads one version of a mod and extracts it into `destination_dir`, without touching the blob store.
+    /// Returns the archive's hash
+    pub async fn download_version_to(
+        &self,
+        thunderstore_version: &Version,
+        expected_hash: Option<&str>,
+        destination_dir: &Path,
+    ) -> Result<String> {
         let download_url = &thunderstore_version.download_url;
-        let file_name = "extractme.zip";
-        let destination_file = destination_dir.join(file_name);
         // download the mod file
-        let response = reqwest::get(download_url).await?;
-        if !response.status().is_success() {
-            return Err(eyre!("Failed to download mod from {}", download_url));
+        let download = async {
+            let response = reqwest::get(download_url).await?;
+            if !response.status().is_success() {
+                return Err(eyre!("Failed to download mod from {}", download_url));
+            }
+            Ok(response.bytes().await?)
+        };
+        let bytes = tokio::select! {
+            bytes = download => bytes?,
+            _ = self.cancel.cancelled() => return Err(Cancelled.into()),
+        };
+        let archive_hash = BlobStore::hash_bytes(&bytes);
+        if let Some(expected_hash) = expected_hash
+            && archive_hash != expected_hash
+        {
+            return Err(eyre!(
+                "Hash mismatch for {}: expected {}, got {}",
+                thunderstore_version.full_name,
+                expected_hash,
+                archive_hash
+            ));
         }
-        let bytes = response.bytes().await?;
+        // Create the destination directory if it does not exist.
+        tokio::fs::create_dir_all(&destination_dir).await?;
+        let destination_file = destination_dir.join("extractme.zip");
         // Save the downloaded bytes to the destination file.
-        println!("destination file: {:?}", &destination_file);
+        debug!("destination file: {:?}", &destination_file);
         tokio::fs::write(&destination_file, &bytes).await?;
         // Extract the zip file contents
-        //println!("Extracting mod files...");
-        self.extract_zip_file(&destination_file, &destination_dir)
+        self.extract_zip_file(&destination_file, destination_dir)
             .await?;
         tokio::fs::remove_file(&destination_file).await?;
-        ModCache::add_mod_config_json(&this_mod, &config)?;
-
-        // update config with the new version
-        let mut local_mod_option = LocalModOptions::new(&config);
-        local_mod_option.enable_mod(&this_mod, &config)?;
-        self.cache_mod_dependancies(&this_mod, real_version).await?;
-
-        self.update_self_from_cache()?;
-        Ok(this_mod.clone())
+        if self.cancel.is_cancelled() {
+            tokio::fs::remove_dir_all(&destination_dir).await?;
+            return Err(Cancelled.into());
+        }
+        Ok(archive_hash)
     }
 
     async fn cache_mod_dependancies(&mut self, mod_to_cache: &Mod, version: String) -> Result<()> {
@@ -113,15 +308,48 @@ impl ModCache {
             let dependancies_ref = self.get_mod_dependencies(mod_to_cache, Some(&version))?;
             dependancies_ref.iter().map(|x| x.uuid.clone()).collect()
         };
-        println!("dependancies: {:?}", dependancies);
+        debug!("dependancies: {:?}", dependancies);
         for dependancy in dependancies {
-            // just downloads the latest version for now, can always extract intended version from the mod's full-name later
-            self.cache_mod_by_mod_id(&dependancy.to_string(), None)
-                .await?;
+            self.cache_dependency(&dependancy).await?;
         }
         Ok(())
     }
 
+    /// Caches a dependency if it isn't yet (marking it auto-installed), and makes sure it's enabled along with
+    /// everything it needs. Dependencies that were disabled earlier get turned back on
+    pub async fn cache_dependency(&mut self, id: &Uuid) -> Result<()> {
+        let was_cached = self.cache_mod_list.iter().any(|x| x.uuid == *id);
+        // just downloads the latest version for now, can always extract intended version from the mod's full-name later
+        let dependency = self.cache_mod_by_mod_id(&id.to_string(), None).await?;
+        if !was_cached {
+            // `cache_mod_by_mod_id` already took care of its dependencies
+            self.options.update(|options| options.set_auto_installed(id, true))?;
+            return Ok(());
+        }
+        // cached mods get skipped by `cache_mod_by_mod_id`, so their dependencies have to be checked here
+        let options = self.options.snapshot();
+        let closure: Vec<Mod> = self
+            .dependency_closure(&[&dependency], &options)
+            .into_iter()
+            .cloned()
+            .collect();
+        let mut to_enable = vec![dependency];
+        for m in closure {
+            if self.cache_mod_list.iter().any(|x| x.uuid == m.uuid) {
+                to_enable.push(m);
+            } else {
+                self.cache_mod_by_mod_id(&m.uuid.to_string(), None).await?;
+                self.options.update(|options| options.set_auto_installed(&m.uuid, true))?;
+            }
+        }
+        self.options.update(|options| {
+            for m in &to_enable {
+                options.enable_mod(m);
+            }
+        })?;
+        Ok(())
+    }
+
     /// Extract a zip file to the specified directory
     async fn extract_zip_file(&self, zip_path: &Path, extract_dir: &Path) -> Result<()> {
         // We need to use a blocking operation within a tokio thread since zip operations are synchronous
@@ -169,13 +397,45 @@ impl ModCache {
         Ok(())
     }
 
+    /// Moves a freshly extracted version into the blob store and writes its manifest
+    pub fn store_version_files(
+        config: &Config,
+        mod_id: &Uuid,
+        version: &str,
+        archive_hash: Option<String>,
+    ) -> Result<VersionManifest> {
+        let version_dir = config
+            .mod_cache_directory
+            .join(mod_id.to_string())
+            .join("versions")
+            .join(version);
+        let manifest = VersionManifest {
+            archive_hash,
+            files: BlobStore::new(config).ingest_directory(&version_dir)?,
+        };
+        manifest.save(config, mod_id, version)?;
+        Ok(manifest)
+    }
+
+    /// Gets the manifest of a cached version. Versions cached before the blob store existed get moved into it here
+    pub fn ensure_version_manifest(
+        config: &Config,
+        mod_id: &Uuid,
+        version: &str,
+    ) -> Result<VersionManifest> {
+        match VersionManifest::load(config, mod_id, version) {
+            Some(manifest) => Ok(manifest),
+            None => ModCache::store_version_files(config, mod_id, version, None),
+        }
+    }
+
     fn add_mod_config_json(mod_to_save: &Mod, config: &Config) -> Result<()> {
         // Define the base cache directory.
         let cache_dir = Path::new(&config.mod_cache_directory);
 
         // Create the mod's directory using its id.
         let mod_dir = cache_dir.join(&mod_to_save.uuid.to_string());
-        println!(
+        debug!(
             "Adding config.json for mod: {} with ID: {}",
             mod_to_save.name, mod_to_save.uuid
         );
@@ -193,15 +453,10 @@ impl ModCache {
     /// Takes in a mod ID and optional version, and returns either the input version or the most recent version
     fn resolve_mod_version(&self, mod_id: &String, version: Option<&String>) -> Result<String> {
         // find the mod
-        let this_mod = self
-            .thunderstore_mod_list
-            .mods
-            .iter()
-            .find(|x| x.uuid.to_string() == *mod_id)
-            .ok_or(eyre!(
-                "resolve_mod_version was passed a mod id that does not exist: {}",
-                mod_id
-            ))?;
+        let this_mod = self.find_mod(&Uuid::parse_str(mod_id)?).ok_or(eyre!(
+            "resolve_mod_version was passed a mod id that does not exist: {}",
+            mod_id
+        ))?;
         // Get the version
         match version {
             // If version is specified, verify it exists for this mod
@@ -217,13 +472,14 @@ impl ModCache {
                         this_mod.name
                     ));
                 }
+                self.rules.check_version(this_mod, version_to_find)?;
                 Ok(version_to_find.clone())
             }
-            // No version specified, return the latest version
-            None => this_mod
-                .versions
-                .first()
-                .ok_or(eyre!("Mod ({}) does not have any versions!", this_mod.name))
+            // No version specified, return the latest version the rules allow
+            None => self
+                .rules
+                .newest_allowed_version(this_mod)
+                .ok_or(eyre!("Mod ({}) does not have any versions the rules allow!", this_mod.name))
                 .map(|v| v.version_number.clone()),
         }
     }
@@ -234,8 +490,8 @@ impl ModCache {
         version: Option<&String>,
     ) -> Result<Mod> {
         let config = Config::new();
-        let rumble_mod_directory = config.rumble_directory.join("Mods");
-        let rumble_user_data_directory = config.rumble_directory.join("UserData");
+        let rumble_mod_directory = config.rumble_mods_directory();
+        let rumble_user_data_directory = config.rumble_user_data_directory();
         let real_version = self.resolve_mod_version(id, version)?;
         let cache_directory = config.mod_cache_directory.join(id).join(real_version);
 
@@ -289,8 +545,11 @@ impl ModCache {
     }
     /// Updates the in‑memory cache from the on‑disk cache directory.
     pub fn update_self_from_cache(&mut self) -> Result<Vec<color_eyre::eyre::Report>> {
+        self.update_self_from_cache_in(&Config::new())
+    }
+    /// Like `update_self_from_cache`, for callers that already have a config
+    pub fn update_self_from_cache_in(&mut self, config: &Config) -> Result<Vec<color_eyre::eyre::Report>> {
         // ── 1. Locate / create the directory ──────────────────────────────
-        let config = Config::new();
         let cache = config.mod_cache_directory.as_path();
 
         if !cache.exists() {
@@ -307,6 +566,7 @@ impl ModCache {
 
         let mods: Vec<Mod> = dir
             .filter_map(|entry| entry.ok()) // ignore IO errors here
+            .filter(|entry| !is_internal_cache_entry(entry))
             .filter_map(|entry| {
                 match ModCache::get_mod_from_dir_in_cache(&entry.path()) {
                     Ok(m) => Some(Ok(m)),
@@ -320,9 +580,45 @@ impl ModCache {
 
         // ── 3. Update self and return ────────────────────────────────────
         self.cache_mod_list = mods;
+        match Rules::load(config) {
+            Ok(rules) => {
+                self.rules = rules;
+                self.rules_error = None;
+            }
+            // the old rules might just be the defaults, so don't install anything until it's fixed
+            Err(e) => {
+                warn!("could not load the rule file: {e:?}");
+                self.rules_error = Some(format!("{e:#}"));
+            }
+        }
         Ok(errors) // empty vec ⇒ no non‑fatal errors
     }
 
+    /// Cheap summary of what's in the cache directory, so changes made outside rumm can be noticed without rescanning.
+    /// Covers each mod folder, its `versions` folder and its `mod_info.json`, since that's what a rescan reads
+    pub fn cache_fingerprint(config: &Config) -> u64 {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+        let modified = |path: &Path| fs::metadata(path).and_then(|x| x.modified()).ok();
+        let mut hasher = DefaultHasher::new();
+        let Ok(dir) = config.mod_cache_directory.read_dir() else {
+            return hasher.finish();
+        };
+        let mut entries: Vec<PathBuf> = dir
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !is_internal_cache_entry(entry))
+            .map(|entry| entry.path())
+            .collect();
+        // read_dir doesn't promise an order
+        entries.sort();
+        for path in entries {
+            path.hash(&mut hasher);
+            modified(&path).hash(&mut hasher);
+            modified(&path.join("versions")).hash(&mut hasher);
+            modified(&path.join("mod_info.json")).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
     /// returns a full mod object if given a path like: `[Cache Dir]/[Mod ID]`
     /// WARNING: returns full mod list from thunderstore - use `update_versions_in_mod` if you want the mod's versions to match the cache
     pub fn get_mod_from_dir_in_cache(path: &Path) -> Result<Mod> {
@@ -373,7 +669,7 @@ impl ModCache {
     /// If the version is not given, uses the latest version
     pub fn get_mod_dependencies<'a>(
         &'a self,
-        mod_to_get: &'a Mod,
+        mod_to_get: &Mod,
         version_to_get: Option<&String>,
     ) -> Result<Vec<&'a Mod>> {
         let real_version =
@@ -403,13 +699,55 @@ impl ModCache {
             })
             .collect()
     }
+    /// Falls back to the cache, so dependencies that were delisted still resolve
     pub fn get_mod_from_full_mod_name(&self, full_name: &String) -> Option<&Mod> {
-        self.thunderstore_mod_list.mods.iter().find(|x| {
-            x.versions
-                .iter()
-                .find(|y| y.full_name == *full_name)
-                .is_some()
-        })
+        let has_version = |x: &&Mod| x.versions.iter().any(|y| y.full_name == *full_name);
+        self.thunderstore_mod_list
+            .mods
+            .iter()
+            .find(has_version)
+            .or_else(|| self.cache_mod_list.iter().find(has_version))
+    }
+
+    /// Looks in the Thunderstore index first, then falls back to the cached `mod_info.json` for delisted mods
+    pub fn find_mod(&self, id: &Uuid) -> Option<&Mod> {
+        self.thunderstore_mod_list
+            .mods
+            .iter()
+            .find(|x| x.uuid == *id)
+            .or_else(|| self.cache_mod_list.iter().find(|x| x.uuid == *id))
+    }
+
+    /// Anything worth warning about for an installed mod, going by the latest Thunderstore index
+    pub fn package_status(&self, installed: &Mod, selected_version: &str) -> Vec<PackageStatus> {
+        let mut statuses = vec![];
+        let listed = self.thunderstore_mod_list.mods.iter().find(|x| x.uuid == installed.uuid);
+        // an empty index just means it was never downloaded, that doesn't make everything delisted
+        if listed.is_none() && !self.thunderstore_mod_list.mods.is_empty() {
+            statuses.push(PackageStatus::Delisted);
+        }
+        let current = listed.unwrap_or(installed);
+        if current.is_deprecated {
+            statuses.push(PackageStatus::Deprecated {
+                replacement: self
+                    .thunderstore_mod_list
+                    .find_replacement(current)
+                    .map(|x| (x.uuid, x.name.clone())),
+            });
+        }
+        if current
+            .versions
+            .iter()
+            .any(|x| x.version_number == selected_version && !x.is_active)
+        {
+            statuses.push(PackageStatus::InactiveVersion(selected_version.to_string()));
+        }
+        statuses
+    }
+
+    /// True if the mod was cached but isn't on Thunderstore anymore
+    pub fn is_delisted(&self, id: &Uuid) -> bool {
+        !self.thunderstore_mod_list.mods.is_empty() && !self.thunderstore_mod_list.mods.iter().any(|x| x.uuid == *id)
     }
     // returns `[mod cache]/[mod id]`
     fn get_mod_file_by_id(&self, config: &Config, id: Uuid) -> Result<PathBuf> {
@@ -422,6 +760,45 @@ impl ModCache {
     fn get_mods_from_cache(&self) -> &Vec<Mod> {
         &self.cache_mod_list
     }
+    pub fn get_thunderstore_mod_list(&self) -> &Arc<ModList> {
+        &self.thunderstore_mod_list
+    }
+    pub fn set_thunderstore_mod_list(&mut self, mod_list: ModList) {
+        self.thunderstore_mod_list = Arc::new(mod_list);
+    }
+    /// Everything the cache does from now on can be cancelled with this token
+    pub fn set_cancel_token(&mut self, token: CancellationToken) {
+        self.cancel = token;
+    }
+
+    pub fn check_cancelled(&self) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        Ok(())
+    }
+
+    /// The options store shared with the rest of the app
+    pub fn options(&self) -> &ModOptionsStore {
+        &self.options
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Why the rule file didn't load, if it didn't
+    pub fn rules_error(&self) -> Option<&str> {
+        self.rules_error.as_deref()
+    }
+
+    /// Errors while the rule file is broken, a typo shouldn't let blocked mods through
+    pub fn check_rules_loaded(&self) -> Result<()> {
+        match &self.rules_error {
+            Some(e) => Err(eyre!("Fix the rule file first: {e}")),
+            None => Ok(()),
+        }
+    }
     // If no string is passed, will return true for any version. Otherwise, will only return true if that version is present in the cache
     pub fn is_mod_in_cache(&self, uuid: &Uuid, version: Option<&String>) -> bool {
         self.cache_mod_list
@@ -437,16 +814,18 @@ impl ModCache {
             })
     }
 
-    // Returns None if the mod is not in cache, returns Some(false) if the version is not in cache
-    pub fn does_mod_have_version(&self, mod_to_check: &Mod, version: &String) -> Option<bool> {
-        Some(
-            mod_to_check
-                .versions
-                .iter()
-                .any(|v| &v.version_number == version),
-        )
+    /// Like `is_mod_in_cache`, but looks in `config`'s cache directory instead of the default one
+    pub fn is_version_cached(config: &Config, mod_id: &Uuid, version: &str) -> bool {
+        config
+            .mod_cache_directory
+            .join(mod_id.to_string())
+            .join("versions")
+            .join(version)
+            .is_dir()
     }
+
     /// Removes the version listed from the cache.
+    #[instrument(skip_all, fields(mod_name = %mod_to_update.name, version = %version.version_number))]
     pub fn remove_version_from_cache(
         &mut self,
         config: &Config,
@@ -459,12 +838,25 @@ impl ModCache {
             .mod_cache_directory
             .join(mod_to_update.uuid.to_string())
             .join("versions")
-            .join(version.version_number);
+            .join(&version.version_number);
         fs::remove_dir_all(path)?;
-        self.update_self_from_cache()?;
+        let manifest_path =
+            VersionManifest::path(config, &mod_to_update.uuid, &version.version_number);
+        if manifest_path.exists() {
+            fs::remove_file(manifest_path)?;
+        }
+        ModCache::remove_unused_blobs(config)?;
+        self.update_self_from_cache_in(config)?;
         Ok(())
     }
+    /// Deletes stored files that no cached (or trashed) version uses anymore. Returns the number of bytes freed
+    pub fn remove_unused_blobs(config: &Config) -> Result<u64> {
+        let mut referenced = VersionManifest::get_all_referenced_hashes(config)?;
+        referenced.extend(Trash::new(config).get_referenced_hashes()?);
+        BlobStore::new(config).remove_unreferenced(&referenced)
+    }
     /// Removes the mod from the cache
+    #[instrument(skip_all, fields(mod_name = %mod_to_remove.name))]
     pub fn remove_mod_from_cache(&mut self, config: &Config, mod_to_remove: &Mod) -> Result<()> {
         let file = self.get_mod_file_by_id(config, mod_to_remove.uuid)?;
         // just another sanity check - make sure we're a decscendant of the cahce directory
@@ -473,65 +865,52 @@ impl ModCache {
             // should be fine though since it must be in the cache directory
             //   (let's just hope nobody changes their cache directory to `/`)
             fs::remove_dir_all(&file)?;
+            ModCache::remove_unused_blobs(config)?;
         };
         Ok(())
     }
 
-    /// Removes all installed versions from the cache except for the newest one
-    pub fn remove_old_versions_from_cache(
+    #[instrument(skip(self, config, mod_to_update), fields(mod_name = %mod_to_update.name))]
+    pub async fn update_mod(
         &mut self,
         config: &Config,
         mod_to_update: &Mod,
+        trigger: UpdateTrigger,
     ) -> Result<()> {
-        // get newest versiom
-        let this_mod = self.prune_extra_versions_from_mod(
-            config,
-            self.cache_mod_list
-                .iter()
-                .find(|x| x.uuid == mod_to_update.uuid)
-                .ok_or(eyre!(
-                    "Could not find mod {} in the config",
-                    mod_to_update.full_name
-                ))?,
-        )?;
-        for version in &this_mod.versions {
-            if version
-                != this_mod
-                    .versions
-                    .first()
-                    .ok_or(eyre!("No versions found for mod {}", this_mod.full_name))?
-            // ignore the first version (hopefully they're organized by release time or this might suck to debug)
-            {
-                self.remove_version_from_cache(config, mod_to_update, version.clone())?;
-            }
-        }
-        Ok(())
-    }
-
-    pub async fn update_mod(&mut self, config: &Config, mod_to_update: &Mod) -> Result<()> {
-        println!("updating mod!");
-        let mut local_options = LocalModOptions::new(config);
-        let mod_version_lock = local_options
-            .get_mod_options(mod_to_update.uuid.to_string())
+        debug!("updating mod!");
+        let (requirement, old_version) = self
+            .options
+            .read(|options| {
+                options
+                    .get_mod_options(mod_to_update.uuid.to_string())
+                    .map(|x| (x.version_requirement.clone(), x.version.clone()))
+            })
             .ok_or(eyre!(
                 "Could not find options for mod: {}",
                 mod_to_update.name
-            ))?
-            .version_lock;
-        if mod_version_lock == true {
-            // mod's version is locked, return
+            ))?;
+        if requirement.is_pinned_to(&old_version) {
+            debug!("pinned to {old_version}, not updating");
             return Ok(());
         }
-        let latest_version = self
+        if self.is_delisted(&mod_to_update.uuid) {
+            info!("no longer on Thunderstore, keeping {old_version}");
+            return Ok(());
+        }
+        let thunderstore_mod = self
             .thunderstore_mod_list
             .mods
             .iter()
             .find(|x| x.uuid == mod_to_update.uuid)
-            .ok_or(eyre!("Could not find mod in Thunderstore Mod List"))?
-            .versions
-            .first() // hopefully versions.first() gets the latest version
-            .ok_or(eyre!("No versions found in mod {}", &mod_to_update.name))?
-            .clone();
+            .ok_or(eyre!("Could not find mod in Thunderstore Mod List"))?;
+        let Some(latest_version) = requirement.newest_matching(thunderstore_mod, &self.rules).cloned() else {
+            if requirement.is_latest() {
+                return Err(eyre!("No allowed versions found in mod {}", &mod_to_update.name));
+            }
+            // not an error, the author just hasn't released anything it accepts (anymore)
+            info!("no version matches {requirement}, keeping {old_version}");
+            return Ok(());
+        };
         // update the mod
         let new_mod = self
             .cache_mod_by_mod_id(
@@ -539,39 +918,108 @@ impl ModCache {
                 Some(&latest_version.version_number),
             )
             .await?;
-        local_options.set_mod_version(&new_mod.uuid, &latest_version.version_number, config)?;
+        self.options.update(|options| {
+            options.set_mod_version(&new_mod.uuid, &latest_version.version_number)
+        })?;
+        if old_version != latest_version.version_number {
+            info!("updated from {} to {}", old_version, latest_version.version_number);
+        }
+        ModHistory::record(
+            config,
+            &new_mod.uuid,
+            &old_version,
+            &latest_version.version_number,
+            trigger,
+        )?;
         Ok(())
     }
 
-    pub async fn update_all_mods(&mut self, config: &Config) -> Result<()> {
-        for mut mod_to_update in self.cache_mod_list.clone() {
-            println!("updating mod: {}", mod_to_update.name);
-            self.update_mod(config, &mut mod_to_update).await?;
+    /// Updates every mod whose update policy is `Auto`. The others are only updated by hand
+    pub async fn update_all_mods(&mut self, config: &Config, trigger: UpdateTrigger) -> Result<()> {
+        let mods = self.auto_update_mods(config);
+        for update in self.available_updates(config) {
+            info!(
+                "{} can update from {} to {}, but is set to notify only",
+                update.mod_to_update.name, update.current, update.newest
+            );
+        }
+        self.update_mods(config, &mods, trigger).await
+    }
+
+    /// Cached mods that `update_all_mods` updates
+    pub fn auto_update_mods(&self, config: &Config) -> Vec<Mod> {
+        self.cache_mod_list
+            .iter()
+            .filter(|m| self.update_policy(config, m) == UpdatePolicy::Auto)
+            .cloned()
+            .collect()
+    }
+
+    /// Mods with no options count as `Manual`, there's nothing to update
+    fn update_policy(&self, config: &Config, m: &Mod) -> UpdatePolicy {
+        self.options.read(|options| {
+            options
+                .get_mod_options(m.uuid.to_string())
+                .map_or(UpdatePolicy::Manual, |x| x.resolved_update_policy(config))
+        })
+    }
+
+    /// Newer versions of `NotifyOnly` mods that their version requirement allows
+    pub fn available_updates(&self, config: &Config) -> Vec<AvailableUpdate> {
+        self.cache_mod_list
+            .iter()
+            .filter(|m| self.update_policy(config, m) == UpdatePolicy::NotifyOnly && !self.is_delisted(&m.uuid))
+            .filter_map(|m| {
+                let (requirement, current) = self.options.read(|options| {
+                    options
+                        .get_mod_options(m.uuid.to_string())
+                        .map(|x| (x.version_requirement.clone(), x.version.clone()))
+                })?;
+                let newest = requirement.newest_matching(self.find_mod(&m.uuid)?, &self.rules)?;
+                (newest.version_number != current).then(|| AvailableUpdate {
+                    mod_to_update: m.clone(),
+                    current,
+                    newest: newest.version_number.clone(),
+                })
+            })
+            .collect()
+    }
+
+    pub async fn update_mods(&mut self, config: &Config, mods: &[Mod], trigger: UpdateTrigger) -> Result<()> {
+        for mod_to_update in mods {
+            self.check_cancelled()?;
+            self.update_mod(config, mod_to_update, trigger).await?;
         }
         Ok(())
     }
 
-    pub async fn sync_all_mods_to_rumble(&self, config: &Config) -> Result<()> {
+    pub async fn sync_all_mods_to_rumble(&self, config: &Config) -> Result<SyncSummary> {
+        self.check_rules_loaded()?;
+        let mut summary = SyncSummary::default();
+        // only checked between mods, so a mod never ends up half synced
         for mod_to_push in self.cache_mod_list.clone() {
-            self.sync_mod_to_rumble(&mod_to_push, config).await?;
+            self.check_cancelled()?;
+            summary.add(self.sync_mod_to_rumble(&mod_to_push, config).await?);
         }
-        Ok(())
+        Ok(summary)
     }
     /// This will check the mod options and will not push if the mod is disabled.
-    pub async fn sync_mod_to_rumble(&self, mod_from_cache: &Mod, config: &Config) -> Result<()> {
-        let rumble_mod_directory = &config.rumble_directory.join("mods");
-        let rumble_user_data_directory = &config.rumble_directory.join("UserData");
-        let local_mod_options = LocalModOptions::new(&config);
+    #[instrument(skip_all, fields(mod_name = %mod_from_cache.name))]
+    pub async fn sync_mod_to_rumble(&self, mod_from_cache: &Mod, config: &Config) -> Result<SyncSummary> {
+        let mut summary = SyncSummary::default();
+        let rumble_mod_directory = &config.rumble_mods_directory();
+        let rumble_user_data_directory = &config.rumble_user_data_directory();
         // get selected version
-        let mod_options = local_mod_options
-            .get_mod_options(mod_from_cache.uuid.to_string())
+        let mod_options = self
+            .options
+            .read(|options| options.get_mod_options(mod_from_cache.uuid.to_string()).cloned())
             .ok_or(eyre!("Mod {} could not be found!", mod_from_cache.name))?;
         let version = mod_options.version.clone();
         let mod_files_cache_path = config
             .mod_cache_directory
             .join(mod_from_cache.uuid.to_string())
             .join("versions")
-            .join(version);
+            .join(&version);
         let mod_cache_mod_dir = mod_files_cache_path.join("Mods");
 
         // If it's not in the rumble path, return an error
@@ -579,19 +1027,29 @@ impl ModCache {
             return Err(eyre!("Rumble could not be found! Check the settings to make sure your rumble path is correct"));
         }
 
+        let mut deployed = DeploymentRecord::load(config);
+        let blocked = self.rules.is_blocked(mod_from_cache);
+        if blocked && mod_options.enabled {
+            warn!("{} is blocked by the rule file, removing it from Rumble", mod_from_cache.name);
+        }
         // if mod is disabled, delete it from the rumble directory and end early
-        if !mod_options.enabled {
+        if !mod_options.enabled || blocked {
             for entry in fs::read_dir(mod_cache_mod_dir.clone())? {
                 let file_name = entry?
                     .file_name()
                     .to_str()
                     .ok_or(eyre!("Could not get file name from directory entry",))?
                     .to_string();
-                ModCache::remove_file_from_directory(file_name, &rumble_mod_directory)?;
+                ModCache::remove_file_from_directory(file_name.clone(), rumble_mod_directory)?;
+                deployed.forget(&rumble_mod_directory.join(file_name));
             }
-            return Ok(());
+            deployed.save(config)?;
+            summary.disabled_mods += 1;
+            return Ok(summary);
         }
 
+        // the manifest has the hash of every file, so files that are already deployed can be skipped
+        let manifest = ModCache::ensure_version_manifest(config, &mod_from_cache.uuid, &version)?;
         // copy mods dir over
         if mod_cache_mod_dir.exists() {
             // mod folder exists, copy any contents to the rumble mod directory
@@ -599,6 +1057,9 @@ impl ModCache {
                 &mod_cache_mod_dir,
                 rumble_mod_directory,
                 true,
+                (&manifest, "Mods"),
+                &mut deployed,
+                &mut summary,
             )?;
         }
         // copy user data over
@@ -609,15 +1070,26 @@ impl ModCache {
                 &mod_cache_user_data_dir,
                 rumble_user_data_directory,
                 false,
+                (&manifest, "UserData"),
+                &mut deployed,
+                &mut summary,
             )?;
         }
-        Ok(())
+        deployed.save(config)?;
+        ModCache::record_version_use(config, &mod_from_cache.uuid, &mod_options.version)?;
+        debug!("synced version {} to Rumble", mod_options.version);
+        summary.enabled_mods += 1;
+        Ok(summary)
     }
 
+    /// `manifest` is the version's manifest plus the folder of it that `source_dir` is, like `Mods`
     fn push_directory_contents_to_other_directory(
         source_dir: &Path,
         receiving_dir: &Path,
         should_overwrite: bool,
+        (manifest, manifest_folder): (&VersionManifest, &str),
+        deployed: &mut DeploymentRecord,
+        summary: &mut SyncSummary,
     ) -> Result<()> {
         // make sure containing folder exists
         if !receiving_dir.exists() {
@@ -630,11 +1102,24 @@ impl ModCache {
                 continue;
             }
 
-            let dest_path =
-                receiving_dir.join(source_path.file_name().ok_or(eyre!("missing filename"))?);
+            let file_name = source_path.file_name().ok_or(eyre!("missing filename"))?;
+            let dest_path = receiving_dir.join(file_name);
+            let hash = manifest
+                .files
+                .get(&format!("{manifest_folder}/{}", file_name.to_string_lossy()))
+                .map(|x| x.hash.as_str());
 
+            // already deployed and untouched since, no need to copy it again
+            if hash.is_some_and(|hash| deployed.is_up_to_date(&dest_path, hash)) {
+                summary.files_unchanged += 1;
+                continue;
+            }
             if !dest_path.exists() || should_overwrite {
                 fs::copy(&source_path, &dest_path)?;
+                summary.files_copied += 1;
+                if let Some(hash) = hash {
+                    deployed.record(&dest_path, hash)?;
+                }
             }
         }
         Ok(())
@@ -642,7 +1127,7 @@ impl ModCache {
 
     /// scary! also incredibly basic, should just switch any instances of this function to just `fs::remove_file()`
     fn remove_file_from_directory(file_name: String, directory: &Path) -> Result<Option<()>> {
-        println!(
+        debug!(
             "removing {file_name} from {}",
             directory
                 .to_str()
@@ -656,9 +1141,60 @@ impl ModCache {
         Ok(Some(()))
     }
 
-    fn check_for_rumble_exe(path: &Path) -> Result<bool> {
+    pub fn check_for_rumble_exe(path: &Path) -> Result<bool> {
         return Ok(path
             .read_dir()?
             .any(|x| x.is_ok_and(|x| x.file_name() == "RUMBLE.exe")));
     }
 }
+
+/// rumm keeps its own folders in the cache directory too (blobs, icons, trash...), and starts their names with a `.`
+/// so the cache scan doesn't mistake them for mods
+fn is_internal_cache_entry(entry: &fs::DirEntry) -> bool {
+    entry.file_name().to_string_lossy().starts_with('.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn update_policies_pick_what_gets_updated() -> Result<()> {
+        let dir = TestDir::new("update-policy");
+        let mut config = dir.config();
+        let versions: &[(&str, &[&str])] = &[("2.0.0", &[]), ("1.0.0", &[])];
+        let auto = Mod::for_test("Owner-Auto", versions);
+        let notify = Mod::for_test("Owner-Notify", versions);
+        let manual = Mod::for_test("Owner-Manual", versions);
+        let default = Mod::for_test("Owner-Default", versions);
+        let all = [&auto, &notify, &manual, &default];
+        let options = ModOptionsStore::new(&config)?;
+        options.update(|options| {
+            for (m, policy) in all.iter().zip([
+                Some(UpdatePolicy::Auto),
+                Some(UpdatePolicy::NotifyOnly),
+                Some(UpdatePolicy::Manual),
+                None,
+            ]) {
+                options.enable_mod(m);
+                options.set_mod_version(&m.uuid, "1.0.0");
+                options.set_update_policy(&m.uuid, policy);
+            }
+        })?;
+        let cache = ModCache::for_test(all.iter().map(|x| (*x).clone()).collect(), options);
+        let ids = |mods: Vec<Mod>| mods.iter().map(|x| x.uuid).collect::<Vec<_>>();
+
+        assert_eq!(ids(cache.auto_update_mods(&config)), [auto.uuid, default.uuid]);
+        config.default_update_policy = UpdatePolicy::Manual;
+        assert_eq!(ids(cache.auto_update_mods(&config)), [auto.uuid]);
+
+        // only notify-only mods get listed, the rest either update on their own or never
+        config.default_update_policy = UpdatePolicy::Auto;
+        let updates = cache.available_updates(&config);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].mod_to_update.uuid, notify.uuid);
+        assert_eq!((updates[0].current.as_str(), updates[0].newest.as_str()), ("1.0.0", "2.0.0"));
+        Ok(())
+    }
+}
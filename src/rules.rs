@@ -0,0 +1,194 @@
+// Rules a team agrees on: packages to hide or block, mods that don't work together, and versions to stay within.
+// Stored at `config/rules.json` by default (see `Config::rules_file`), so a shared copy can just be dropped in.
+// Packages are matched by their full name (`Owner-Name`) or just their name
+
+use std::fs;
+
+use color_eyre::eyre::{Context, Result, eyre};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::config_and_such::Config;
+use crate::thunderstore::{Mod, Version};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rules {
+    #[serde(default)]
+    pub packages: Vec<PackageRule>,
+    #[serde(default)]
+    pub incompatible: Vec<IncompatibilityRule>,
+    #[serde(default)]
+    pub versions: Vec<VersionRule>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PackageAction {
+    /// Not shown in the browser, but can still be installed (as a dependency, say)
+    Hide,
+    /// Never installed or synced to Rumble
+    Block,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageRule {
+    pub package: String,
+    pub action: PackageAction,
+    pub reason: String,
+}
+
+/// Two mods that shouldn't be enabled at the same time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IncompatibilityRule {
+    pub mods: [String; 2],
+    pub reason: String,
+}
+
+impl std::fmt::Display for IncompatibilityRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} and {} don't work together: {}", self.mods[0], self.mods[1], self.reason)
+    }
+}
+
+/// Keeps a mod within a range of versions. Both ends are inclusive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionRule {
+    pub package: String,
+    #[serde(default)]
+    pub min: Option<String>,
+    #[serde(default)]
+    pub max: Option<String>,
+    pub reason: String,
+}
+
+impl VersionRule {
+    fn allows(&self, version: &str) -> bool {
+        // versions that aren't semver can't be compared, so let them through
+        let Ok(version) = semver::Version::parse(version) else {
+            return true;
+        };
+        let is_at_least = |min: &String| semver::Version::parse(min).is_ok_and(|min| version >= min);
+        let is_at_most = |max: &String| semver::Version::parse(max).is_ok_and(|max| version <= max);
+        self.min.as_ref().is_none_or(is_at_least) && self.max.as_ref().is_none_or(is_at_most)
+    }
+}
+
+impl Default for Rules {
+    /// What a new rule file starts with
+    fn default() -> Self {
+        Self {
+            packages: vec![PackageRule {
+                package: "GaleModManager".to_string(),
+                action: PackageAction::Hide,
+                reason: "It's a mod manager, not a mod".to_string(),
+            }],
+            incompatible: vec![],
+            versions: vec![],
+        }
+    }
+}
+
+fn matches(package: &str, m: &Mod) -> bool {
+    package == m.full_name || package == m.name
+}
+
+impl Rules {
+    /// Writes the default rules if there's no rule file yet
+    pub fn load(config: &Config) -> Result<Self> {
+        let path = &config.rules_file;
+        if !path.exists() {
+            info!("no rule file found, making one at {:?}", path);
+            let rules = Rules::default();
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, serde_json::to_string_pretty(&rules)?)?;
+            return Ok(rules);
+        }
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).wrap_err_with(|| format!("The rule file {:?} is not valid", path))
+    }
+
+    pub fn package_rule(&self, m: &Mod) -> Option<&PackageRule> {
+        self.packages.iter().find(|x| matches(&x.package, m))
+    }
+
+    /// Hidden and blocked packages both stay out of the browser
+    pub fn is_hidden(&self, m: &Mod) -> bool {
+        self.package_rule(m).is_some()
+    }
+
+    pub fn is_blocked(&self, m: &Mod) -> bool {
+        self.package_rule(m).is_some_and(|x| x.action == PackageAction::Block)
+    }
+
+    /// Errors if the package is blocked
+    pub fn check_allowed(&self, m: &Mod) -> Result<()> {
+        match self.package_rule(m) {
+            Some(rule) if rule.action == PackageAction::Block => {
+                Err(eyre!("{} is blocked by the rule file: {}", m.name, rule.reason))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn version_rule(&self, m: &Mod) -> Option<&VersionRule> {
+        self.versions.iter().find(|x| matches(&x.package, m))
+    }
+
+    pub fn is_version_allowed(&self, m: &Mod, version: &str) -> bool {
+        self.version_rule(m).is_none_or(|x| x.allows(version))
+    }
+
+    /// Errors if the version is outside the mod's allowed range
+    pub fn check_version(&self, m: &Mod, version: &str) -> Result<()> {
+        match self.version_rule(m) {
+            Some(rule) if !rule.allows(version) => Err(eyre!(
+                "{} {version} is not allowed by the rule file: {}",
+                m.name,
+                rule.reason
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Versions are newest first, so this is the newest one the rules allow.
+    /// Inactive versions were pulled by their author, so they're skipped too
+    pub fn newest_allowed_version<'a>(&self, m: &'a Mod) -> Option<&'a Version> {
+        m.versions
+            .iter()
+            .find(|x| x.is_active && self.is_version_allowed(m, &x.version_number))
+    }
+
+    /// Rules that would be broken by enabling `enabling` on top of `enabled`
+    pub fn find_conflicts(&self, enabling: &[&Mod], enabled: &[&Mod]) -> Vec<&IncompatibilityRule> {
+        let hits = |package: &str, mods: &[&Mod]| mods.iter().any(|m| matches(package, m));
+        self.incompatible
+            .iter()
+            .filter(|rule| {
+                let [a, b] = &rule.mods;
+                (hits(a, enabling) && (hits(b, enabled) || hits(b, enabling)))
+                    || (hits(b, enabling) && (hits(a, enabled) || hits(a, enabling)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_rules_are_inclusive() -> Result<()> {
+        let rules: Rules = serde_json::from_str(
+            r#"{ "versions": [{ "package": "Owner-Mod", "min": "1.2.0", "max": "2.0.0", "reason": "testing" }] }"#,
+        )?;
+        let rule = &rules.versions[0];
+        assert!(!rule.allows("1.1.9"));
+        assert!(rule.allows("1.2.0"));
+        assert!(rule.allows("2.0.0"));
+        assert!(!rule.allows("2.0.1"));
+        // can't compare it, so it's let through
+        assert!(rule.allows("latest"));
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+// Scratch directories for tests. They're deleted when dropped, so a failing assertion doesn't leave them behind
+
+use std::fs;
+use std::path::PathBuf;
+
+use uuid::Uuid;
+
+use crate::config_and_such::Config;
+
+pub struct TestDir {
+    pub path: PathBuf,
+}
+
+impl TestDir {
+    /// Makes an empty `rumm-[name]-test-[random]` in the system temp directory
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("rumm-{name}-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&path).expect("could not create a test directory");
+        Self { path }
+    }
+
+    /// Default settings with every path inside this directory. Never reads the real `config/config.json`
+    pub fn config(&self) -> Config {
+        Config::in_directory(&self.path)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
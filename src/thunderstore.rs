@@ -42,6 +42,9 @@ pub struct Version {
     pub version_number: String,
     pub website_url: String,
 }
+/// Where the latest response from Thunderstore is saved
+pub const MOD_LIST_CACHE_PATH: &str = "config/thunderstore-mods.json";
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ModList {
     pub mods: Vec<Mod>,
@@ -71,6 +74,47 @@ impl Mod {
         Ok(parsed)
     }
 }
+#[cfg(test)]
+impl Mod {
+    /// A made-up package for tests. `full_name` is like `Owner-Name`, versions are newest first with their dependency strings
+    pub fn for_test(full_name: &str, versions: &[(&str, &[&str])]) -> Self {
+        let (owner, name) = full_name.split_once('-').unwrap_or(("Someone", full_name));
+        Mod {
+            uuid: Uuid::new_v4(),
+            name: name.to_string(),
+            full_name: full_name.to_string(),
+            owner: owner.to_string(),
+            package_url: String::new(),
+            donation_link: None,
+            date_created: String::new(),
+            date_updated: String::new(),
+            rating_score: 0,
+            is_pinned: false,
+            is_deprecated: false,
+            has_nsfw_content: false,
+            categories: vec![],
+            versions: versions
+                .iter()
+                .map(|(version_number, dependencies)| Version {
+                    date_created: String::new(),
+                    dependencies: dependencies.iter().map(|x| x.to_string()).collect(),
+                    description: String::new(),
+                    download_url: String::new(),
+                    downloads: 0,
+                    file_size: 0,
+                    full_name: format!("{full_name}-{version_number}"),
+                    icon: String::new(),
+                    is_active: true,
+                    name: name.to_string(),
+                    uuid4: Uuid::new_v4().to_string(),
+                    version_number: version_number.to_string(),
+                    website_url: String::new(),
+                })
+                .collect(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -83,6 +127,32 @@ mod tests {
             .unwrap();
         Ok(())
     }
+
+    fn deprecated_with(description: &str) -> Mod {
+        let mut m = Mod::for_test("Old-OldMod", &[("1.0.0", &[])]);
+        m.is_deprecated = true;
+        m.versions[0].description = description.to_string();
+        m
+    }
+
+    #[test]
+    fn replacements_come_from_the_description() {
+        let new_mod = Mod::for_test("Someone-NewMod", &[("1.0.0", &[])]);
+        let author = Mod::for_test("Someone-Ulvak", &[("1.0.0", &[])]);
+        let longer = Mod::for_test("Someone-NewModExtras", &[("1.0.0", &[])]);
+        let list = ModList {
+            mods: vec![author.clone(), longer.clone(), new_mod.clone()],
+        };
+        let found = |description: &str| list.find_replacement(&deprecated_with(description)).map(|x| x.uuid);
+        assert_eq!(found("Deprecated, see Someone-NewMod."), Some(new_mod.uuid));
+        assert_eq!(found("Please use NewMod instead"), Some(new_mod.uuid));
+        assert_eq!(found("Replaced by newmod"), Some(new_mod.uuid));
+        assert_eq!(found("Moved to Someone-NewModExtras"), Some(longer.uuid));
+        // just mentioning a name isn't enough
+        assert_eq!(found("Made by Ulvak, thanks for playing"), None);
+        assert_eq!(found("Works well with NewMod"), None);
+        assert_eq!(found(""), None);
+    }
 }
 
 impl ModList {
@@ -96,10 +166,61 @@ impl ModList {
         }
     }
 
-    pub fn sort(&self, metric: &SortType) -> ModList {
-        let mut new_list = self.clone();
-        new_list.sort_self(metric);
-        new_list
+    /// Guesses what replaces a deprecated mod from its latest description, like "use Owner-NewMod instead"
+    pub fn find_replacement(&self, deprecated: &Mod) -> Option<&Mod> {
+        let description = deprecated.versions.first()?.description.to_lowercase();
+        let candidates = || {
+            self.mods
+                .iter()
+                .filter(|x| x.uuid != deprecated.uuid && !x.is_deprecated)
+        };
+        // a full name is pretty unambiguous, a bare name only counts right after "use" and the like.
+        // Not after a plain "by", that's usually "made by <author>"
+        let full_names: Vec<&str> = description
+            .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+            .collect();
+        let words: Vec<&str> = description
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter(|x| !x.is_empty())
+            .collect();
+        let points_to = |i: usize| {
+            matches!(
+                words[..i],
+                [.., "use" | "to" | "see"] | [.., "replaced" | "superseded", "by"]
+            )
+        };
+        candidates()
+            .find(|x| full_names.contains(&x.full_name.to_lowercase().as_str()))
+            .or_else(|| {
+                candidates().find(|x| {
+                    let name = x.name.to_lowercase();
+                    (1..words.len()).any(|i| words[i] == name && points_to(i))
+                })
+            })
+    }
+
+    /// Adds any mods and versions from `other` that this list doesn't have yet. Existing entries are left alone
+    pub fn merge(&mut self, other: ModList) {
+        for other_mod in other.mods {
+            match self.mods.iter_mut().find(|x| x.uuid == other_mod.uuid) {
+                Some(existing) => {
+                    for version in other_mod.versions {
+                        if !existing
+                            .versions
+                            .iter()
+                            .any(|x| x.version_number == version.version_number)
+                        {
+                            existing.versions.push(version);
+                        }
+                    }
+                    // keep the newest version first
+                    existing
+                        .versions
+                        .sort_by(|a, b| b.date_created.cmp(&a.date_created));
+                }
+                None => self.mods.push(other_mod),
+            }
+        }
     }
 
     pub fn sort_self(&mut self, metric: &SortType) {
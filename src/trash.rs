@@ -0,0 +1,275 @@
+// Deleted mods and versions get moved here instead of being removed outright, so deleting can be undone.
+// Each entry is `[Cache Dir]/.trash/[Entry ID]/{entry.json, files/, manifest.json}`.
+// Undo only lasts for one session, so the gui empties the trash when it starts
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use color_eyre::eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::blob_store::VersionManifest;
+use crate::config_and_such::Config;
+use crate::mod_cache::ModCache;
+use crate::thunderstore::{Mod, Version};
+
+/// Inside the mod cache
+const TRASH_DIR_NAME: &str = ".trash";
+const ENTRY_FILE_NAME: &str = "entry.json";
+
+/// What a trash entry holds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TrashEntry {
+    mod_id: Uuid,
+    /// `None` if the whole mod was deleted
+    version: Option<String>,
+    /// Seconds since the unix epoch
+    trashed_at: u64,
+}
+
+/// Something the cache moved to the trash, enough to put it back
+#[derive(Debug, Clone)]
+pub struct TrashedItem {
+    pub mod_to_change: Mod,
+    /// `None` if the whole mod was deleted
+    pub version: Option<String>,
+    pub trash_id: Uuid,
+}
+
+impl std::fmt::Display for TrashedItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{} {version}", self.mod_to_change.name),
+            None => write!(f, "{}", self.mod_to_change.name),
+        }
+    }
+}
+
+pub struct Trash {
+    root: PathBuf,
+    cache_directory: PathBuf,
+}
+
+impl Trash {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            root: config.mod_cache_directory.join(TRASH_DIR_NAME),
+            cache_directory: config.mod_cache_directory.clone(),
+        }
+    }
+
+    /// Moves a mod's whole cache directory into the trash. Returns the ID of the new entry
+    pub fn put_mod(&self, mod_id: &Uuid) -> Result<Uuid> {
+        let (id, entry_dir) = self.new_entry(mod_id, None)?;
+        move_dir(&self.cache_directory.join(mod_id.to_string()), &entry_dir.join("files"))?;
+        Ok(id)
+    }
+
+    /// Moves one cached version (and its manifest) into the trash. Returns the ID of the new entry
+    pub fn put_version(&self, config: &Config, mod_id: &Uuid, version: &str) -> Result<Uuid> {
+        let (id, entry_dir) = self.new_entry(mod_id, Some(version))?;
+        move_dir(&self.version_dir(mod_id, version), &entry_dir.join("files"))?;
+        let manifest_path = VersionManifest::path(config, mod_id, version);
+        if manifest_path.exists() {
+            fs::rename(manifest_path, entry_dir.join("manifest.json"))?;
+        }
+        Ok(id)
+    }
+
+    /// Moves an entry back to where it came from
+    pub fn restore(&self, config: &Config, id: &Uuid) -> Result<()> {
+        let entry_dir = self.root.join(id.to_string());
+        let entry = Self::load_entry(&entry_dir)?;
+        match &entry.version {
+            None => {
+                let mod_dir = self.cache_directory.join(entry.mod_id.to_string());
+                if mod_dir.exists() {
+                    return Err(eyre!("The mod was added again since it was deleted"));
+                }
+                move_dir(&entry_dir.join("files"), &mod_dir)?;
+            }
+            Some(version) => {
+                let version_dir = self.version_dir(&entry.mod_id, version);
+                if version_dir.exists() {
+                    return Err(eyre!("Version {version} was downloaded again since it was removed"));
+                }
+                if !self.cache_directory.join(entry.mod_id.to_string()).exists() {
+                    return Err(eyre!("The mod was deleted, so version {version} can't be put back"));
+                }
+                move_dir(&entry_dir.join("files"), &version_dir)?;
+                let manifest_path = entry_dir.join("manifest.json");
+                if manifest_path.exists() {
+                    let destination = VersionManifest::path(config, &entry.mod_id, version);
+                    fs::create_dir_all(destination.parent().ok_or(eyre!("manifest path has no parent"))?)?;
+                    fs::rename(manifest_path, destination)?;
+                }
+            }
+        }
+        fs::remove_dir_all(entry_dir)?;
+        Ok(())
+    }
+
+    /// Permanently deletes everything in the trash
+    pub fn empty(&self) -> Result<()> {
+        if self.root.exists() {
+            debug!("emptying the trash");
+            fs::remove_dir_all(&self.root)?;
+        }
+        Ok(())
+    }
+
+    /// Blob hashes used by anything in the trash, so cleaning up the blob store doesn't break a restore
+    pub fn get_referenced_hashes(&self) -> Result<HashSet<String>> {
+        let mut hashes = HashSet::new();
+        if !self.root.exists() {
+            return Ok(hashes);
+        }
+        for entry_dir in fs::read_dir(&self.root)? {
+            let entry_dir = entry_dir?.path();
+            let mut manifest_paths = vec![entry_dir.join("manifest.json")];
+            // whole mods keep their manifests where they always were
+            if let Ok(dir) = fs::read_dir(entry_dir.join("files").join("manifests")) {
+                manifest_paths.extend(dir.filter_map(|x| Some(x.ok()?.path())));
+            }
+            for path in manifest_paths {
+                let Ok(contents) = fs::read_to_string(path) else {
+                    continue;
+                };
+                let manifest: VersionManifest = serde_json::from_str(&contents)?;
+                hashes.extend(manifest.files.into_values().map(|x| x.hash));
+            }
+        }
+        Ok(hashes)
+    }
+
+    fn new_entry(&self, mod_id: &Uuid, version: Option<&str>) -> Result<(Uuid, PathBuf)> {
+        let id = Uuid::new_v4();
+        let entry_dir = self.root.join(id.to_string());
+        fs::create_dir_all(&entry_dir)?;
+        let entry = TrashEntry {
+            mod_id: *mod_id,
+            version: version.map(|x| x.to_string()),
+            trashed_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        };
+        fs::write(entry_dir.join(ENTRY_FILE_NAME), serde_json::to_string_pretty(&entry)?)?;
+        Ok((id, entry_dir))
+    }
+
+    fn load_entry(entry_dir: &Path) -> Result<TrashEntry> {
+        let contents = fs::read_to_string(entry_dir.join(ENTRY_FILE_NAME))
+            .map_err(|_| eyre!("That's no longer in the trash"))?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    fn version_dir(&self, mod_id: &Uuid, version: &str) -> PathBuf {
+        self.cache_directory
+            .join(mod_id.to_string())
+            .join("versions")
+            .join(version)
+    }
+}
+
+/// The trash lives inside the cache directory, so this is just a rename
+fn move_dir(from: &Path, to: &Path) -> Result<()> {
+    if !from.exists() {
+        return Err(eyre!("{:?} does not exist", from));
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(from, to)?;
+    Ok(())
+}
+
+impl ModCache {
+    /// Like `remove_mod_from_cache`, but the mod can be put back with `restore_from_trash`
+    pub fn trash_mod(&mut self, config: &Config, mod_to_remove: &Mod) -> Result<TrashedItem> {
+        let trash_id = Trash::new(config).put_mod(&mod_to_remove.uuid)?;
+        self.update_self_from_cache_in(config)?;
+        Ok(TrashedItem {
+            mod_to_change: mod_to_remove.clone(),
+            version: None,
+            trash_id,
+        })
+    }
+
+    /// Like `remove_version_from_cache`, but the version can be put back with `restore_from_trash`
+    pub fn trash_version(&mut self, config: &Config, mod_to_update: &Mod, version: &Version) -> Result<TrashedItem> {
+        let trash_id = Trash::new(config).put_version(config, &mod_to_update.uuid, &version.version_number)?;
+        self.update_self_from_cache_in(config)?;
+        Ok(TrashedItem {
+            mod_to_change: mod_to_update.clone(),
+            version: Some(version.version_number.clone()),
+            trash_id,
+        })
+    }
+
+    /// Trashes every cached version of the mod except the selected one and the newest one.
+    /// Pushes onto `trashed` as it goes, so whatever moved before a failure is still listed
+    pub fn trash_old_versions(
+        &mut self,
+        config: &Config,
+        mod_to_update: &Mod,
+        trashed: &mut Vec<TrashedItem>,
+    ) -> Result<()> {
+        let selected = self.options().read(|options| {
+            options
+                .get_mod_options(mod_to_update.uuid.to_string())
+                .map(|x| x.version.clone())
+        });
+        let cached = self.prune_extra_versions_from_mod(config, mod_to_update)?;
+        let newest = cached.versions.first().map(|x| x.version_number.clone());
+        for version in &cached.versions {
+            if Some(&version.version_number) == selected.as_ref() || Some(&version.version_number) == newest.as_ref() {
+                continue;
+            }
+            trashed.push(self.trash_version(config, &cached, version)?);
+        }
+        Ok(())
+    }
+
+    /// Puts the items back, newest first so versions of a deleted mod go back after the mod itself
+    pub fn restore_from_trash(&mut self, config: &Config, items: &[TrashedItem]) -> Result<()> {
+        let trash = Trash::new(config);
+        for item in items.iter().rev() {
+            trash.restore(config, &item.trash_id)?;
+        }
+        self.update_self_from_cache_in(config)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn trashed_version_can_be_restored() -> Result<()> {
+        let dir = TestDir::new("trash");
+        let config = dir.config();
+        let trash = Trash::new(&config);
+        let mod_id = Uuid::new_v4();
+        let dll = config
+            .mod_cache_directory
+            .join(mod_id.to_string())
+            .join("versions")
+            .join("1.0.0")
+            .join("Mods")
+            .join("Some.dll");
+        fs::create_dir_all(dll.parent().unwrap())?;
+        fs::write(&dll, b"mod")?;
+
+        let id = trash.put_version(&config, &mod_id, "1.0.0")?;
+        assert!(!dll.exists());
+        trash.restore(&config, &id)?;
+        assert_eq!(fs::read(&dll)?, b"mod");
+        // restoring twice doesn't work, it's not in the trash anymore
+        assert!(trash.restore(&config, &id).is_err());
+        Ok(())
+    }
+}
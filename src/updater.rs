@@ -1,51 +1,229 @@
-use color_eyre::eyre::Result;
-use self_update::{cargo_crate_version, update::Release};
+// Lets rumm update itself. Releases come from `Config::release_source`, which is the GitHub releases API by default,
+// but anything serving the same JSON works (handy for testing against a local server).
+// Every release needs a `SHA256SUMS` asset next to the binaries, nothing gets installed without a matching checksum
 
-/// Allows the program to update itself from the latest github release
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use color_eyre::eyre::{Result, eyre};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::config_and_such::{Config, UpdateChannel};
+
+/// Name of the checksum asset, in the same format `sha256sum` writes
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+/// The binary for this platform, same names as the release workflow uploads
+const BINARY_ASSET: &str = if cfg!(target_os = "windows") { "rumm.exe" } else { "rumm" };
+
+/// One release, as the GitHub API returns it. Only the fields rumm uses
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Release notes, in markdown
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    /// Tags look like `v1.2.3`. `None` if it isn't semver
+    pub fn version(&self) -> Option<semver::Version> {
+        semver::Version::parse(self.tag_name.trim_start_matches('v')).ok()
+    }
+
+    pub fn title(&self) -> &str {
+        self.name.as_deref().filter(|x| !x.is_empty()).unwrap_or(&self.tag_name)
+    }
+
+    fn asset(&self, name: &str) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|x| x.name == name)
+    }
+}
+
+/// Finds releases newer than the one that's running
 pub struct Updater {
     releases: Vec<Release>,
-    current_release_version: String,
-    repo_owner: String,
-    repo_name: String,
+    current_version: semver::Version,
+    channel: UpdateChannel,
+    release_source: String,
 }
 
 impl Updater {
-    pub async fn new() -> Result<Self> {
-        let repo_owner = "michaelgoldenn".to_string();
-        let repo_name = "rumm".to_string();
-        let releases = self_update::backends::github::ReleaseList::configure()
-            .repo_owner(&repo_owner)
-            .repo_name(&repo_name)
-            .build()?
-            .fetch()?;
-        Ok(Updater {
-            releases,
-            current_release_version: cargo_crate_version!().to_string(),
-            repo_owner,
-            repo_name,
-        })
-    }
-    pub fn get_release_names(&self) -> Vec<String> {
-        self.releases.iter().map(|x| x.name.clone()).collect()
-    }
-    pub fn get_release_versions(&self) -> Vec<String> {
-        self.releases.iter().map(|x| x.version.clone()).collect()
-    }
-    pub fn get_releases(&self) -> &Vec<Release> {
-        &self.releases
-    }
-    pub fn get_releases_mut(&mut self) -> &mut Vec<Release> {
-        &mut self.releases
-    }
-    pub fn sync_releases_with_remote(&mut self) -> Result<()> {
-        self.releases = self_update::backends::github::ReleaseList::configure()
-            .repo_owner(&self.repo_owner)
-            .repo_name(&self.repo_name)
-            .build()?
-            .fetch()?;
+    pub async fn new(config: &Config) -> Result<Self> {
+        let mut updater = Updater {
+            releases: vec![],
+            current_version: semver::Version::parse(env!("CARGO_PKG_VERSION"))?,
+            channel: config.update_channel,
+            release_source: config.release_source.clone(),
+        };
+        updater.sync_releases_with_remote().await?;
+        Ok(updater)
+    }
+
+    pub async fn sync_releases_with_remote(&mut self) -> Result<()> {
+        self.releases = client()?
+            .get(&self.release_source)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
         Ok(())
     }
-    pub fn is_mod_up_to_date(&self) -> bool {
-        self.releases.first().map_or(false, |x| x.version == self.current_release_version)
+
+    /// Releases on this channel that are newer than the running version, newest first
+    pub fn newer_releases(&self) -> Vec<&Release> {
+        newer_releases(&self.releases, &self.current_version, self.channel)
+    }
+}
+
+/// Downloads the release's binary, checks it against the published checksum, and swaps it in for the running one.
+/// Returns where the new binary is, for `restart`
+pub async fn install(release: &Release) -> Result<PathBuf> {
+    let asset = release
+        .asset(BINARY_ASSET)
+        .ok_or(eyre!("{} has no {BINARY_ASSET} to download", release.title()))?;
+    let checksums = release.asset(CHECKSUMS_ASSET).ok_or(eyre!(
+        "{} has no published checksum, so it can't be installed safely",
+        release.title()
+    ))?;
+    let client = client()?;
+    let checksums = client
+        .get(&checksums.browser_download_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let expected = find_checksum(&checksums, BINARY_ASSET)
+        .ok_or(eyre!("{CHECKSUMS_ASSET} doesn't list {BINARY_ASSET}"))?;
+    info!("downloading {} from {}", release.title(), asset.browser_download_url);
+    let bytes = client
+        .get(&asset.browser_download_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let actual = format!("{:x}", Sha256::digest(&bytes));
+    if !actual.eq_ignore_ascii_case(&expected) {
+        return Err(eyre!(
+            "The download doesn't match its checksum (expected {expected}, got {actual}), not installing it"
+        ));
+    }
+
+    // has to be grabbed before replacing, on linux it ends in " (deleted)" afterwards
+    let current_exe = env::current_exe()?;
+    // next to the running binary, so the swap is a rename on the same drive
+    let staged = current_exe.with_file_name(format!(".{BINARY_ASSET}.update"));
+    fs::write(&staged, &bytes)?;
+    make_executable(&staged)?;
+    let result = self_update::self_replace::self_replace(&staged);
+    let _ = fs::remove_file(&staged);
+    result?;
+    info!("installed {}, restart to use it", release.title());
+    Ok(current_exe)
+}
+
+/// Starts the new binary with the same arguments, then exits this one
+pub fn restart(exe: &Path) -> Result<()> {
+    std::process::Command::new(exe)
+        .args(env::args().skip(1))
+        .spawn()?;
+    std::process::exit(0);
+}
+
+fn client() -> Result<reqwest::Client> {
+    // the GitHub API turns away requests without a user agent
+    Ok(reqwest::Client::builder()
+        .user_agent(concat!("rumm/", env!("CARGO_PKG_VERSION")))
+        .build()?)
+}
+
+fn newer_releases<'a>(
+    releases: &'a [Release],
+    current: &semver::Version,
+    channel: UpdateChannel,
+) -> Vec<&'a Release> {
+    let mut newer: Vec<(semver::Version, &Release)> = releases
+        .iter()
+        .filter(|x| !x.draft)
+        .filter(|x| channel == UpdateChannel::Prerelease || !x.prerelease)
+        .filter_map(|x| Some((x.version()?, x)))
+        .filter(|(version, _)| version > current)
+        .collect();
+    newer.sort_by(|a, b| b.0.cmp(&a.0));
+    newer.into_iter().map(|(_, x)| x).collect()
+}
+
+/// Finds a file's hash in `sha256sum` output, like `abc123  rumm.exe` (binary mode puts a `*` before the name)
+fn find_checksum(checksums: &str, file_name: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (hash, name) = line.trim().split_once(char::is_whitespace)?;
+        (name.trim().trim_start_matches('*') == file_name).then(|| hash.to_string())
+    })
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(tag: &str, prerelease: bool) -> Release {
+        Release {
+            tag_name: tag.to_string(),
+            name: None,
+            body: None,
+            prerelease,
+            draft: false,
+            assets: vec![],
+        }
+    }
+
+    #[test]
+    fn prereleases_only_show_on_their_channel() {
+        let releases = vec![release("v0.3.0-beta.1", true), release("v0.2.0", false), release("v0.1.0", false)];
+        let current = semver::Version::parse("0.1.0").unwrap();
+        let stable = newer_releases(&releases, &current, UpdateChannel::Stable);
+        assert_eq!(stable.iter().map(|x| x.tag_name.as_str()).collect::<Vec<_>>(), ["v0.2.0"]);
+        let prerelease = newer_releases(&releases, &current, UpdateChannel::Prerelease);
+        assert_eq!(prerelease[0].tag_name, "v0.3.0-beta.1");
+    }
+
+    #[test]
+    fn checksums_are_found_by_file_name() {
+        // sha256sum output, text mode has two spaces and binary mode a `*` before the name
+        let checksums = "abc  rumm\ndef *rumm.exe\n";
+        assert_eq!(find_checksum(checksums, "rumm"), Some("abc".to_string()));
+        assert_eq!(find_checksum(checksums, "rumm.exe"), Some("def".to_string()));
+        assert_eq!(find_checksum(checksums, "rumm.zip"), None);
+        assert_eq!(find_checksum("", "rumm"), None);
     }
 }
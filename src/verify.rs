@@ -0,0 +1,236 @@
+// "Verify installation": like Steam's "verify integrity of game files", but for the enabled mods.
+// Hashes the cached copy of each mod's selected version and everything it deployed to Rumble.
+// Damaged cache entries get downloaded again (checked against the original archive hash), then broken or
+// missing files in Rumble get copied over again by a normal sync
+
+use std::fs;
+use std::path::Path;
+
+use color_eyre::eyre::{Result, eyre};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::blob_store::{BlobStore, DeploymentRecord, VersionManifest};
+use crate::config_and_such::Config;
+use crate::mod_cache::ModCache;
+use crate::thunderstore::{Mod, Version};
+
+/// Where repairs get downloaded before they replace the damaged copy
+const STAGING_DIR_NAME: &str = ".staging";
+
+/// What `ModCache::verify_installation` found and fixed
+#[derive(Debug, Clone, Default)]
+pub struct VerifySummary {
+    pub mods_checked: usize,
+    /// Files in Rumble, the cached copies get checked too but aren't counted
+    pub files_checked: usize,
+    /// Files in Rumble that were missing or damaged and got copied again
+    pub files_repaired: usize,
+    /// Cached versions that were damaged and got downloaded again
+    pub versions_redownloaded: usize,
+    /// UserData files that differ from the mod's copy. Mods save their settings there, so they're left alone
+    pub user_data_changed: usize,
+    /// Mods that couldn't be repaired, with why
+    pub failed: Vec<String>,
+}
+
+impl std::fmt::Display for VerifySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} mods ({} files), {} files repaired, {} versions downloaded again",
+            self.mods_checked, self.files_checked, self.files_repaired, self.versions_redownloaded
+        )?;
+        if self.user_data_changed > 0 {
+            write!(f, ", {} UserData files changed by mods (left alone)", self.user_data_changed)?;
+        }
+        if !self.failed.is_empty() {
+            write!(f, ", {} could not be repaired: {}", self.failed.len(), self.failed.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+impl ModCache {
+    /// Checks every enabled mod's cached and deployed files, and repairs whatever is damaged or missing
+    pub async fn verify_installation(&self, config: &Config) -> Result<VerifySummary> {
+        if !matches!(ModCache::check_for_rumble_exe(&config.rumble_directory), Ok(true)) {
+            return Err(eyre!("Rumble could not be found! Check the settings to make sure your rumble path is correct"));
+        }
+        let mut summary = VerifySummary::default();
+        for m in self.cache_mod_list.clone() {
+            // only checked between mods, so a mod never ends up half repaired
+            self.check_cancelled()?;
+            let Some(options) = self
+                .options()
+                .read(|options| options.get_mod_options(m.uuid.to_string()).cloned())
+            else {
+                continue;
+            };
+            if !options.enabled || self.rules().is_blocked(&m) {
+                continue;
+            }
+            if let Err(e) = self.verify_mod(config, &m, &options.version, &mut summary).await {
+                warn!("could not repair {}: {e}", m.name);
+                summary.failed.push(format!("{} ({e})", m.name));
+            }
+        }
+        info!("verified the installation: {summary}");
+        Ok(summary)
+    }
+
+    async fn verify_mod(&self, config: &Config, m: &Mod, version: &str, summary: &mut VerifySummary) -> Result<()> {
+        summary.mods_checked += 1;
+        let version_dir = config
+            .mod_cache_directory
+            .join(m.uuid.to_string())
+            .join("versions")
+            .join(version);
+        let mut manifest = ModCache::ensure_version_manifest(config, &m.uuid, version)?;
+        let damaged = find_damaged_files(&version_dir, &manifest);
+        if !damaged.is_empty() {
+            warn!("{} {version} has damaged files in the cache: {}", m.name, damaged.join(", "));
+            let thunderstore_version = self
+                .find_mod(&m.uuid)
+                .and_then(|x| x.versions.iter().find(|x| x.version_number == version))
+                .ok_or(eyre!("version {version} can't be downloaded again, it's not on Thunderstore"))?
+                .clone();
+            manifest = self
+                .redownload_version(config, &m.uuid, &thunderstore_version, manifest.archive_hash.as_deref())
+                .await?;
+            summary.versions_redownloaded += 1;
+        }
+
+        // everything rumm would have deployed for this version
+        let mut deployed = DeploymentRecord::load(config);
+        let mut needs_sync = !damaged.is_empty();
+        for (key, file) in &manifest.files {
+            let (destination, is_user_data) = match key.split_once('/') {
+                Some(("Mods", name)) if !name.contains('/') => (config.rumble_mods_directory().join(name), false),
+                Some(("UserData", name)) if !name.contains('/') => {
+                    (config.rumble_user_data_directory().join(name), true)
+                }
+                _ => continue,
+            };
+            summary.files_checked += 1;
+            if !destination.is_file() {
+                needs_sync = true;
+                summary.files_repaired += 1;
+                continue;
+            }
+            if BlobStore::hash_file(&destination)? == file.hash {
+                continue;
+            }
+            if is_user_data {
+                summary.user_data_changed += 1;
+            } else {
+                // forgetting it makes the sync copy it again
+                deployed.forget(&destination);
+                needs_sync = true;
+                summary.files_repaired += 1;
+            }
+        }
+        if needs_sync {
+            deployed.save(config)?;
+            self.sync_mod_to_rumble(m, config).await?;
+        }
+        Ok(())
+    }
+}
+
+impl ModCache {
+    /// The archive hash recorded for a cached version, but only if every cached file still matches the manifest.
+    /// None if it isn't cached, was never hashed, or is damaged
+    pub fn intact_archive_hash(config: &Config, mod_id: &Uuid, version: &str) -> Option<String> {
+        let manifest = VersionManifest::load(config, mod_id, version)?;
+        let version_dir = config.mod_cache_directory.join(mod_id.to_string()).join("versions").join(version);
+        if !find_damaged_files(&version_dir, &manifest).is_empty() {
+            return None;
+        }
+        manifest.archive_hash
+    }
+
+    /// Downloads a version again and swaps it in for the cached copy, whatever state that copy is in
+    pub async fn redownload_version(
+        &self,
+        config: &Config,
+        mod_id: &Uuid,
+        thunderstore_version: &Version,
+        expected_hash: Option<&str>,
+    ) -> Result<VersionManifest> {
+        let version = &thunderstore_version.version_number;
+        let version_dir = config.mod_cache_directory.join(mod_id.to_string()).join("versions").join(version);
+        // download next to the cache first, a failed or cancelled download leaves the old copy alone
+        let staging_dir = config
+            .mod_cache_directory
+            .join(STAGING_DIR_NAME)
+            .join(format!("{mod_id}-{version}"));
+        if staging_dir.exists() {
+            fs::remove_dir_all(&staging_dir)?;
+        }
+        let archive_hash = match self.download_version_to(thunderstore_version, expected_hash, &staging_dir).await {
+            Ok(x) => x,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&staging_dir);
+                return Err(e);
+            }
+        };
+        // the damaged blobs have to go before it's stored, otherwise the new files would just link back to them
+        if let Some(old) = VersionManifest::load(config, mod_id, version) {
+            let blobs = BlobStore::new(config);
+            for key in find_damaged_files(&version_dir, &old) {
+                blobs.remove_blob(&old.files[&key].hash)?;
+            }
+        }
+        if version_dir.exists() {
+            fs::remove_dir_all(&version_dir)?;
+        }
+        fs::create_dir_all(version_dir.parent().ok_or(eyre!("version directory has no parent"))?)?;
+        fs::rename(&staging_dir, &version_dir)?;
+        ModCache::store_version_files(config, mod_id, version, Some(archive_hash))
+    }
+}
+
+/// Files of a cached version that are missing or don't match their manifest hash, by manifest key
+fn find_damaged_files(version_dir: &Path, manifest: &VersionManifest) -> Vec<String> {
+    manifest
+        .files
+        .iter()
+        .filter(|(key, file)| !BlobStore::hash_file(&version_dir.join(key)).is_ok_and(|x| x == file.hash))
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blob_store::ManifestFile;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn damaged_and_missing_cache_files_are_found() -> Result<()> {
+        let dir = TestDir::new("verify");
+        let version_dir = dir.path.clone();
+        fs::create_dir_all(version_dir.join("Mods"))?;
+        fs::write(version_dir.join("Mods").join("Fine.dll"), b"fine")?;
+        fs::write(version_dir.join("Mods").join("Flipped.dll"), b"bit rot")?;
+        let file = |contents: &[u8]| ManifestFile {
+            hash: BlobStore::hash_bytes(contents),
+            size: contents.len() as u64,
+        };
+        let manifest = VersionManifest {
+            archive_hash: None,
+            files: [
+                ("Mods/Fine.dll".to_string(), file(b"fine")),
+                ("Mods/Flipped.dll".to_string(), file(b"original")),
+                ("Mods/Gone.dll".to_string(), file(b"gone")),
+            ]
+            .into(),
+        };
+        assert_eq!(
+            find_damaged_files(&version_dir, &manifest),
+            ["Mods/Flipped.dll", "Mods/Gone.dll"]
+        );
+        Ok(())
+    }
+}
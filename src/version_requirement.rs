@@ -0,0 +1,114 @@
+// Which versions a mod is allowed to update to. Written like Cargo's: `=1.4.2` pins one version,
+// `~1.4` only takes patch updates, `^1` anything below 2.0. `latest` (the default) follows the newest release
+
+use std::str::FromStr;
+
+use color_eyre::eyre::{Report, Result, eyre};
+use serde::{Deserialize, Serialize};
+
+use crate::rules::Rules;
+use crate::thunderstore::{Mod, Version};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum VersionRequirement {
+    #[default]
+    Latest,
+    Matching(semver::VersionReq),
+}
+
+impl VersionRequirement {
+    /// Only `version` itself. Errors if it isn't semver, rather than quietly following the newest release
+    pub fn exact(version: &str) -> Result<Self> {
+        match format!("={version}").parse()? {
+            VersionRequirement::Latest => Err(eyre!("\"{version}\" can't be pinned")),
+            requirement => Ok(requirement),
+        }
+    }
+
+    /// True if this only allows `version`
+    pub fn is_pinned_to(&self, version: &str) -> bool {
+        VersionRequirement::exact(version).is_ok_and(|x| x == *self)
+    }
+
+    pub fn is_latest(&self) -> bool {
+        *self == VersionRequirement::Latest
+    }
+
+    /// Versions that aren't semver only match `latest`
+    pub fn matches(&self, version: &str) -> bool {
+        match self {
+            VersionRequirement::Latest => true,
+            VersionRequirement::Matching(requirement) => {
+                semver::Version::parse(version).is_ok_and(|x| requirement.matches(&x))
+            }
+        }
+    }
+
+    /// The newest version that this and the rule file both allow. Pulled versions are skipped
+    pub fn newest_matching<'a>(&self, m: &'a Mod, rules: &Rules) -> Option<&'a Version> {
+        m.versions
+            .iter()
+            .find(|x| x.is_active && self.matches(&x.version_number) && rules.is_version_allowed(m, &x.version_number))
+    }
+}
+
+impl FromStr for VersionRequirement {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() || s.eq_ignore_ascii_case("latest") || s == "*" {
+            return Ok(VersionRequirement::Latest);
+        }
+        semver::VersionReq::parse(s)
+            .map(VersionRequirement::Matching)
+            .map_err(|e| eyre!("\"{s}\" is not a version requirement: {e}"))
+    }
+}
+
+impl TryFrom<String> for VersionRequirement {
+    type Error = Report;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<VersionRequirement> for String {
+    fn from(value: VersionRequirement) -> Self {
+        value.to_string()
+    }
+}
+
+impl std::fmt::Display for VersionRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionRequirement::Latest => write!(f, "latest"),
+            VersionRequirement::Matching(requirement) => write!(f, "{requirement}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requirements_match_like_cargo() -> Result<()> {
+        let tilde: VersionRequirement = "~1.4".parse()?;
+        assert!(tilde.matches("1.4.9"));
+        assert!(!tilde.matches("1.5.0"));
+        let caret: VersionRequirement = "^1".parse()?;
+        assert!(caret.matches("1.9.0"));
+        assert!(!caret.matches("2.0.0"));
+        assert!(VersionRequirement::exact("1.4.2")?.matches("1.4.2"));
+        assert!(!VersionRequirement::exact("1.4.2")?.matches("1.4.3"));
+        assert!(VersionRequirement::exact("1.4.2")?.is_pinned_to("1.4.2"));
+        assert!(VersionRequirement::exact("not semver").is_err());
+        assert!(VersionRequirement::exact("").is_err());
+        assert!("latest".parse::<VersionRequirement>()?.is_latest());
+        assert!("not a version".parse::<VersionRequirement>().is_err());
+        Ok(())
+    }
+}